@@ -23,7 +23,12 @@ pub struct AABB {
 impl AABB {
     pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self { AABB { min, max } }
 
-    pub fn hit(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> bool {
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.intersect(ray, t_min, t_max).is_some()
+    }
+
+    // parametric range of the ray inside the box, clipped to [t_min, t_max]
+    pub fn intersect(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> Option<(f32, f32)> {
         for a in 0..3 {
             let inv_d = 1.0 / ray.direction()[a];
             let t0 = (self.min[a] - ray.origin()[a]) * inv_d;
@@ -32,9 +37,9 @@ impl AABB {
             t_min = t_min.max(t0);
             t_max = t_max.min(t1);
             if t_max <= t_min {
-                return false
+                return None
             }
         }
-        true
+        Some((t_min, t_max))
     }
 }
\ No newline at end of file
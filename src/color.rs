@@ -0,0 +1,112 @@
+use nalgebra::{Matrix3, Vector3};
+use crate::texture::{Texture, ScalarField};
+
+// the sRGB transfer function, from encoded values in [0, 1] to linear intensity
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// the inverse of srgb_to_linear, for encoding linear intensity in [0, 1] for display
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+// gaussian with different widths below and above the mean
+fn lobe(x: f32, mean: f32, below: f32, above: f32) -> f32 {
+    let t = (x - mean) / if x < mean { below } else { above };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 colour matching functions at a wavelength in nm, in the multi-lobe fit of Wyman et al.
+fn cie_xyz(wavelength: f32) -> Vector3<f32> {
+    Vector3::new(
+        1.056 * lobe(wavelength, 599.8, 37.9, 31.0) + 0.362 * lobe(wavelength, 442.0, 16.0, 26.7) - 0.065 * lobe(wavelength, 501.1, 20.4, 26.2),
+        0.821 * lobe(wavelength, 568.8, 46.9, 40.5) + 0.286 * lobe(wavelength, 530.9, 16.3, 31.1),
+        1.217 * lobe(wavelength, 437.0, 11.8, 36.0) + 0.681 * lobe(wavelength, 459.0, 26.0, 13.8))
+}
+
+// linear sRGB colour of a black body at a temperature in kelvin, scaled so that the largest
+// component is 1. the brightness of a black body grows with the fourth power of temperature
+pub fn blackbody(temperature: f32) -> Vector3<f32> {
+    if temperature <= 0.0 {
+        return Vector3::zeros()
+    }
+    // Planck's law up to a constant, integrated over the visible spectrum
+    let xyz: Vector3<f32> = (380..=780).step_by(5)
+        .map(|nm| {
+            let wavelength = nm as f32 * 1e-9;
+            let radiance = 1.0 / (wavelength.powi(5) * ((1.4388e-2 / (wavelength * temperature)).exp() - 1.0));
+            radiance * cie_xyz(nm as f32)
+        })
+        .sum();
+    let xyz_to_rgb = Matrix3::new(
+        3.2406, -1.5372, -0.4986,
+        -0.9689, 1.8758, 0.0415,
+        0.0557, -0.2040, 1.0570);
+    let rgb = (xyz_to_rgb * xyz).map(|c| c.max(0.0));
+    let max = rgb.max();
+    if max > 0.0 && max.is_finite() { rgb / max } else { Vector3::zeros() }
+}
+
+// colours at increasing positions, interpolated linearly between them and held beyond the ends
+#[derive(Clone)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Vector3<f32>)>
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f32, Vector3<f32>)>) -> Self {
+        assert!(!stops.is_empty(), "color ramp needs at least one stop");
+        stops.sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        ColorRamp { stops }
+    }
+
+    pub fn value(&self, x: f32) -> Vector3<f32> {
+        let first = &self.stops[0];
+        let last = &self.stops[self.stops.len() - 1];
+        if x <= first.0 {
+            first.1
+        } else if x >= last.0 {
+            last.1
+        } else {
+            let next = self.stops.iter().position(|s| s.0 > x).unwrap();
+            let (x0, c0) = self.stops[next - 1];
+            let (x1, c1) = self.stops[next];
+            c0.lerp(&c1, (x - x0) / (x1 - x0))
+        }
+    }
+}
+
+// emission of a temperature field in kelvin, intensity is the radiance at 1000K
+pub struct BlackbodyTexture<F: ScalarField> {
+    temperature: F,
+    intensity: f32
+}
+
+impl<F: ScalarField> BlackbodyTexture<F> {
+    pub fn new(temperature: F, intensity: f32) -> Self { BlackbodyTexture { temperature, intensity } }
+}
+
+impl<F: ScalarField> Texture for BlackbodyTexture<F> {
+    fn value(&self, _u: f32, _v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        let temperature = self.temperature.at(p);
+        self.intensity * (temperature / 1000.0).powi(4) * blackbody(temperature)
+    }
+}
+
+// a scalar field mapped through a colour ramp and scaled by intensity
+pub struct RampTexture<F: ScalarField> {
+    field: F,
+    ramp: ColorRamp,
+    intensity: f32
+}
+
+impl<F: ScalarField> RampTexture<F> {
+    pub fn new(field: F, ramp: ColorRamp, intensity: f32) -> Self { RampTexture { field, ramp, intensity } }
+}
+
+impl<F: ScalarField> Texture for RampTexture<F> {
+    fn value(&self, _u: f32, _v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        self.intensity * self.ramp.value(self.field.at(p))
+    }
+}
@@ -0,0 +1,84 @@
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::aabb;
+use crate::aabb::AABB;
+
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference
+}
+
+impl Operation {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Operation::Union => in_left || in_right,
+            Operation::Intersection => in_left && in_right,
+            Operation::Difference => in_left && !in_right
+        }
+    }
+}
+
+// combines two closed hitables by walking their intervals along the ray
+pub struct CSG<A: Hitable, B: Hitable> {
+    operation: Operation,
+    left: A,
+    right: B
+}
+
+impl<A: Hitable, B: Hitable> CSG<A, B> {
+    pub fn new(operation: Operation, left: A, right: B) -> Self { CSG { operation, left, right } }
+}
+
+impl<A: Hitable, B: Hitable> Hitable for CSG<A, B> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.intervals(ray).into_iter()
+            .flat_map(|(enter, exit)| vec![enter, exit])
+            .find(|hit| hit.t > t_min && hit.t < t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        let left = self.left.bounding_box(t0, t1);
+        let right = self.right.bounding_box(t0, t1);
+        match (&self.operation, left, right) {
+            (Operation::Union, Some(left), Some(right)) => Some(aabb::surrounding_box(&left, &right)),
+            (Operation::Intersection, Some(left), Some(right)) =>
+                Some(AABB::new(left.min.sup(&right.min), left.max.inf(&right.max))),
+            (Operation::Difference, left, _) => left,
+            _ => None
+        }
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<(HitRecord, HitRecord)> {
+        // every boundary as (hit, from left, entering), sorted along the ray
+        let mut events: Vec<(HitRecord, bool, bool)> = Vec::new();
+        for (from_left, intervals) in [(true, self.left.intervals(ray)), (false, self.right.intervals(ray))] {
+            for (enter, exit) in intervals {
+                events.push((enter, from_left, true));
+                events.push((exit, from_left, false));
+            }
+        }
+        events.sort_unstable_by(|a, b| a.0.t.partial_cmp(&b.0.t).unwrap());
+        let mut intervals = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        let (mut in_left, mut in_right) = (false, false);
+        for (mut hit, from_left, entering) in events {
+            if from_left { in_left = entering } else { in_right = entering }
+            // surfaces of the right hitable bound a difference from the inside out
+            if !from_left && matches!(self.operation, Operation::Difference) {
+                hit.normal = -hit.normal;
+                hit.geometric_normal = -hit.geometric_normal;
+                hit.bitangent_sign = -hit.bitangent_sign;
+            }
+            match (enter, self.operation.inside(in_left, in_right)) {
+                (None, true) => enter = Some(hit),
+                (Some(e), false) => {
+                    intervals.push((e, hit));
+                    enter = None;
+                },
+                _ => ()
+            }
+        }
+        intervals
+    }
+}
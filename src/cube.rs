@@ -1,35 +1,119 @@
+use std::f32;
 use nalgebra::Vector3;
 use crate::ray::Ray;
-use crate::hitable::{Hitable, HitableList, HitRecord, FlipNormals};
+use crate::hitable;
+use crate::hitable::{Hitable, HitableList, HitRecord};
 use crate::material::Material;
-use crate::rect::{AARect, Plane};
+use crate::quad::Quad;
 use crate::aabb::AABB;
 
+// corner and edges of the sides of a parallelepiped, in the order +a, -a, +b, -b, +c, -c.
+// seen from outside with b up, u runs right and v up on every side, and the sides join
+// up like a cross with -c in the middle: +a, -c, -a and +c from left to right, +b above and -b below
+fn sides(origin: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> [(Vector3<f32>, Vector3<f32>, Vector3<f32>); 6] {
+    // mirror left handed edges along a, so that all sides face outwards
+    let left_handed = a.cross(&b).dot(&c) < 0.0;
+    let (o, a) = if left_handed { (origin + a, -a) } else { (origin, a) };
+    let mut sides = [
+        (o + a + c, -c, b),
+        (o, c, b),
+        (o + a + b, -a, c),
+        (o + a + c, -a, -c),
+        (o + c, a, b),
+        (o + a, -a, b)
+    ];
+    if left_handed {
+        sides.swap(0, 1);
+    }
+    sides
+}
+
+// moves the uv of a side into its cell of a 4x3 cubemap cross
+struct CubemapSide<H: Hitable> {
+    side: H,
+    cell: (f32, f32)
+}
+
+impl<H: Hitable> Hitable for CubemapSide<H> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.side.hit(ray, t_min, t_max).map(|mut hit| {
+            hit.u = (self.cell.0 + hit.u) / 4.0;
+            hit.v = (self.cell.1 + hit.v) / 3.0;
+            hit
+        })
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.side.bounding_box(t0, t1)
+    }
+}
+
 pub struct Cube {
-    p_min: Vector3<f32>,
-    p_max: Vector3<f32>,
+    bbox: AABB,
     sides: HitableList
 }
 
 impl Cube {
     pub fn new<M: Material + Clone + 'static>(p_min: Vector3<f32>, p_max: Vector3<f32>, material: M) -> Self {
-        let mut sides = HitableList::default();
-        sides.push(AARect::new(Plane::XY, p_min.x, p_max.x, p_min.y, p_max.y, p_max.z, material.clone()));
-        sides.push(FlipNormals::new(AARect::new(Plane::XY, p_min.x, p_max.x, p_min.y, p_max.y, p_min.z, material.clone())));
-        sides.push(AARect::new(Plane::ZX, p_min.z, p_max.z, p_min.x, p_max.x, p_max.y, material.clone()));
-        sides.push(FlipNormals::new(AARect::new(Plane::ZX, p_min.z, p_max.z, p_min.x, p_max.x, p_min.y, material.clone())));
-        sides.push(AARect::new(Plane::YZ, p_min.y, p_max.y, p_min.z, p_max.z, p_max.x, material.clone()));
-        sides.push(FlipNormals::new(AARect::new(Plane::YZ, p_min.y, p_max.y, p_min.z, p_max.z, p_min.x, material)));
-        Cube { p_min, p_max, sides }
+        let size = p_max - p_min;
+        Cube::oriented(p_min, Vector3::new(size.x, 0.0, 0.0), Vector3::new(0.0, size.y, 0.0), Vector3::new(0.0, 0.0, size.z), material)
+    }
+
+    // a parallelepiped with a corner in origin and edges a, b and c
+    pub fn oriented<M: Material + Clone + 'static>(origin: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>, material: M) -> Self {
+        Cube::oriented_with_faces(origin, a, b, c, std::array::from_fn(|_| material.clone()))
+    }
+
+    // one material for each side, in the order +x, -x, +y, -y, +z, -z
+    #[allow(dead_code)]
+    pub fn with_faces<M: Material + 'static>(p_min: Vector3<f32>, p_max: Vector3<f32>, materials: [M; 6]) -> Self {
+        let size = p_max - p_min;
+        Cube::oriented_with_faces(p_min, Vector3::new(size.x, 0.0, 0.0), Vector3::new(0.0, size.y, 0.0), Vector3::new(0.0, 0.0, size.z), materials)
+    }
+
+    // one material for each side, in the order +a, -a, +b, -b, +c, -c
+    pub fn oriented_with_faces<M: Material + 'static>(origin: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>, materials: [M; 6]) -> Self {
+        let mut list = HitableList::default();
+        for (&(q, u, v), material) in sides(origin, a, b, c).iter().zip(materials) {
+            list.push(Quad::new(q, u, v, material));
+        }
+        Cube { bbox: Cube::parallelepiped_box(origin, a, b, c), sides: list }
+    }
+
+    // a single texture laid out as a horizontal cross of 4x3 squares, +x, -z, -x and +z
+    // in the middle row from left to right, with +y above and -y below -z
+    pub fn cubemap<M: Material + Clone + 'static>(p_min: Vector3<f32>, p_max: Vector3<f32>, material: M) -> Self {
+        let size = p_max - p_min;
+        let (a, b, c) = (Vector3::new(size.x, 0.0, 0.0), Vector3::new(0.0, size.y, 0.0), Vector3::new(0.0, 0.0, size.z));
+        let cells = [(0.0, 1.0), (2.0, 1.0), (1.0, 2.0), (1.0, 0.0), (3.0, 1.0), (1.0, 1.0)];
+        let mut list = HitableList::default();
+        for (&(q, u, v), cell) in sides(p_min, a, b, c).iter().zip(cells) {
+            list.push(CubemapSide { side: Quad::new(q, u, v, material.clone()), cell });
+        }
+        Cube { bbox: Cube::parallelepiped_box(p_min, a, b, c), sides: list }
+    }
+
+    fn parallelepiped_box(origin: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> AABB {
+        let corners = [origin + a, origin + b, origin + c, origin + a + b, origin + b + c, origin + a + c, origin + a + b + c];
+        let (min, max) = corners.iter().fold((origin, origin), |(min, max), p| (min.inf(p), max.sup(p)));
+        AABB::new(min, max)
     }
 }
 
 impl Hitable for Cube {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        self.sides.hit(&ray, t_min, t_max)
+        self.sides.hit(ray, t_min, t_max)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<(HitRecord, HitRecord)> {
+        // a box is convex, so there is at most one span inside it
+        self.sides.hit(ray, -f32::MAX, f32::MAX)
+            .and_then(|enter| self.sides.hit(ray, hitable::step_past(enter.t), f32::MAX).map(|exit| (enter, exit)))
+            .into_iter()
+            .collect()
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
-        Some(AABB { min: self.p_min, max: self.p_max })
+        Some(self.bbox)
     }
-}
\ No newline at end of file
+}
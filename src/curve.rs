@@ -0,0 +1,172 @@
+use std::f32;
+use std::sync::Arc;
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::material::Material;
+use crate::bvh::BVH;
+use crate::aabb::AABB;
+
+// number of pieces every curve of a group is split into, each with its own bounding box
+const PIECES: usize = 4;
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 { (1.0 - t) * a + t * b }
+
+fn bezier(cp: &[Vector3<f32>; 4], u: f32) -> (Vector3<f32>, Vector3<f32>) {
+    let a = [cp[0].lerp(&cp[1], u), cp[1].lerp(&cp[2], u), cp[2].lerp(&cp[3], u)];
+    let b = [a[0].lerp(&a[1], u), a[1].lerp(&a[2], u)];
+    let derivative = 3.0 * (b[1] - b[0]);
+    // the derivative vanishes at a degenerate end, fall back to the chord
+    let derivative = if derivative.norm_squared() > 0.0 { derivative } else { cp[3] - cp[0] };
+    (b[0].lerp(&b[1], u), derivative)
+}
+
+fn blossom(cp: &[Vector3<f32>; 4], u0: f32, u1: f32, u2: f32) -> Vector3<f32> {
+    let a = [cp[0].lerp(&cp[1], u0), cp[1].lerp(&cp[2], u0), cp[2].lerp(&cp[3], u0)];
+    let b = [a[0].lerp(&a[1], u1), a[1].lerp(&a[2], u1)];
+    b[0].lerp(&b[1], u2)
+}
+
+// control points of the part of the curve between u0 and u1
+fn segment(cp: &[Vector3<f32>; 4], u0: f32, u1: f32) -> [Vector3<f32>; 4] {
+    [blossom(cp, u0, u0, u0), blossom(cp, u0, u0, u1), blossom(cp, u0, u1, u1), blossom(cp, u1, u1, u1)]
+}
+
+// a cubic bezier curve, seen as a flat ribbon facing the ray whose width goes from
+// width0 to width1 along the curve. u runs along the curve and v across the ribbon
+pub struct Curve<M: Material> {
+    points: [Vector3<f32>; 4],
+    width: (f32, f32),
+    u_range: (f32, f32),
+    max_depth: usize,
+    material: Arc<M>
+}
+
+impl<M: Material> Curve<M> {
+    #[allow(dead_code)]
+    pub fn new(points: [Vector3<f32>; 4], width0: f32, width1: f32, material: M) -> Self {
+        Curve::piece(points, (width0, width1), (0.0, 1.0), Arc::new(material))
+    }
+
+    fn piece(curve: [Vector3<f32>; 4], width: (f32, f32), u_range: (f32, f32), material: Arc<M>) -> Self {
+        let points = segment(&curve, u_range.0, u_range.1);
+        // subdivide until the segments are flat to within a small part of the width
+        let l0 = (0..2)
+            .map(|i| (points[i] - 2.0 * points[i + 1] + points[i + 2]).amax())
+            .fold(0.0, f32::max);
+        let eps = f32::max(width.0, width.1) * 0.05;
+        let max_depth = if l0 > 0.0 && eps > 0.0 {
+            ((std::f32::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0).clamp(0.0, 10.0) as usize
+        } else {
+            0
+        };
+        Curve { points, width, u_range, max_depth, material }
+    }
+
+    fn width_at(&self, u: f32) -> f32 { lerp(u, self.width.0, self.width.1) }
+
+    // cp are the control points in ray space, where the ray starts at the origin and runs along z.
+    // returns the distance along the ray, u and the offset across the ribbon in [-1, 1]
+    fn intersect(&self, cp: &[Vector3<f32>; 4], u0: f32, u1: f32, depth: usize, z_min: f32, z_max: f32) -> Option<(f32, f32, f32)> {
+        let half_width = 0.5 * f32::max(self.width_at(u0), self.width_at(u1));
+        let (min, max) = cp.iter().fold((cp[0], cp[0]), |(min, max), p| (min.inf(p), max.sup(p)));
+        if min.x - half_width > 0.0 || max.x + half_width < 0.0 ||
+            min.y - half_width > 0.0 || max.y + half_width < 0.0 ||
+            min.z - half_width > z_max || max.z + half_width < z_min {
+            return None
+        }
+        if depth > 0 {
+            let u_mid = 0.5 * (u0 + u1);
+            let left = segment(cp, 0.0, 0.5);
+            let right = segment(cp, 0.5, 1.0);
+            let first = self.intersect(&left, u0, u_mid, depth - 1, z_min, z_max);
+            let z_max = first.map_or(z_max, |(z, _, _)| z);
+            return self.intersect(&right, u_mid, u1, depth - 1, z_min, z_max).or(first)
+        }
+        // the ray must pass between the planes through the ends perpendicular to the curve
+        if (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x) < 0.0 ||
+            (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x) < 0.0 {
+            return None
+        }
+        // closest point to the ray, approximating the segment by a line
+        let direction = (cp[3] - cp[0]).xy();
+        let denominator = direction.norm_squared();
+        if denominator == 0.0 {
+            return None
+        }
+        let w = (-cp[0].xy().dot(&direction) / denominator).clamp(0.0, 1.0);
+        let u = lerp(w, u0, u1);
+        let hit_width = self.width_at(u);
+        let (pc, dpcdw) = bezier(cp, w);
+        let distance2 = pc.x * pc.x + pc.y * pc.y;
+        if distance2 > hit_width * hit_width * 0.25 || pc.z <= z_min || pc.z >= z_max {
+            return None
+        }
+        let across = Vector3::new(-dpcdw.y, dpcdw.x, 0.0).normalize();
+        let h = (-pc.dot(&across) / (0.5 * hit_width)).clamp(-1.0, 1.0);
+        Some((pc.z, u, h))
+    }
+}
+
+impl<M: Material> Hitable for Curve<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let length = ray.direction().norm();
+        let z = ray.direction() / length;
+        let x = if z.x.abs() > 0.9 { Vector3::y() } else { Vector3::x() }.cross(&z).normalize();
+        let y = z.cross(&x);
+        let to_ray = |p: &Vector3<f32>| {
+            let p = p - ray.origin();
+            Vector3::new(p.dot(&x), p.dot(&y), p.dot(&z))
+        };
+        let cp = [to_ray(&self.points[0]), to_ray(&self.points[1]), to_ray(&self.points[2]), to_ray(&self.points[3])];
+        let (u_min, u_max) = self.u_range;
+        self.intersect(&cp, u_min, u_max, self.max_depth, t_min * length, t_max * length).map(|(z, u, h)| {
+            let t = z / length;
+            let (_, derivative) = bezier(&self.points, (u - u_min) / (u_max - u_min));
+            let tangent = derivative.normalize();
+            // shade the flat ribbon like the cylinder it stands for
+            let facing = -ray.direction() + tangent * ray.direction().dot(&tangent);
+            let facing = if facing.norm_squared() > 0.0 { facing.normalize() } else { -ray.direction() / length };
+            let normal = (1.0 - h * h).sqrt() * facing + h * tangent.cross(&facing);
+            // v goes across along tangent x facing, against normal x tangent
+            HitRecord { t, u, v: 0.5 * (h + 1.0), p: ray.point_at_parameter(t), normal, geometric_normal: normal, tangent, bitangent_sign: -1.0, material: self.material.as_ref() }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let half_width = 0.5 * f32::max(self.width_at(self.u_range.0), self.width_at(self.u_range.1));
+        let padding = Vector3::new(half_width, half_width, half_width);
+        let (min, max) = self.points.iter().fold((self.points[0], self.points[0]), |(min, max), p| (min.inf(p), max.sup(p)));
+        Some(AABB::new(min - padding, max + padding))
+    }
+}
+
+// many curves sharing a material, like the strands of hair or the blades of grass. each curve is
+// split into pieces that are bounded much tighter than the whole curve, and grouped in a BVH
+pub struct Curves {
+    bvh: BVH
+}
+
+impl Curves {
+    pub fn new<M: Material + 'static>(curves: Vec<[Vector3<f32>; 4]>, width0: f32, width1: f32, material: M) -> Self {
+        let material = Arc::new(material);
+        let pieces: Vec<Box<dyn Hitable>> = curves.iter()
+            .flat_map(|&points| (0..PIECES).map(move |i| (points, i)))
+            .map(|(points, i)| {
+                let u_range = (i as f32 / PIECES as f32, (i + 1) as f32 / PIECES as f32);
+                Box::new(Curve::piece(points, (width0, width1), u_range, material.clone())) as Box<dyn Hitable>
+            })
+            .collect();
+        Curves { bvh: BVH::new(pieces, 0.0, 1.0) }
+    }
+}
+
+impl Hitable for Curves {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
@@ -0,0 +1,149 @@
+use std::f32;
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::material::Material;
+use crate::perlin::Perlin;
+use crate::mesh::hit_triangle;
+use crate::aabb::AABB;
+
+pub struct Heightfield<M: Material> {
+    heights: Vec<f32>,
+    normals: Vec<Vector3<f32>>,
+    nx: usize,
+    nz: usize,
+    origin: Vector3<f32>,
+    size: Vector3<f32>,
+    bbox: AABB,
+    material: M
+}
+
+impl<M: Material> Heightfield<M> {
+    // heights are a row major grid of nx * nz samples in [0, 1], the grid spans size.x and size.z
+    // from origin, and heights are scaled by size.y
+    pub fn new(heights: Vec<f32>, nx: usize, nz: usize, origin: Vector3<f32>, size: Vector3<f32>, material: M) -> Self {
+        assert!(nx > 1 && nz > 1, "heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), nx * nz, "heightfield samples do not match grid size");
+        let (min, max) = heights.iter().fold((f32::MAX, f32::MIN), |(min, max), &h| (min.min(h), max.max(h)));
+        let bbox = AABB::new(
+            Vector3::new(origin.x, origin.y + size.y * min - 0.0001, origin.z),
+            Vector3::new(origin.x + size.x, origin.y + size.y * max + 0.0001, origin.z + size.z));
+        let mut heightfield = Heightfield { heights, normals: Vec::new(), nx, nz, origin, size, bbox, material };
+        heightfield.normals = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| heightfield.vertex_normal(i, j))
+            .collect();
+        heightfield
+    }
+
+    // heights are turbulence sampled at frequency times the local grid coordinates,
+    // normalized to [0, 1] so that size.y is the height of the highest peak
+    pub fn from_noise(noise: &Perlin, nx: usize, nz: usize, frequency: f32, origin: Vector3<f32>, size: Vector3<f32>, material: M) -> Self {
+        let mut heights: Vec<f32> = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let x = size.x * i as f32 / (nx - 1) as f32;
+                let z = size.z * j as f32 / (nz - 1) as f32;
+                noise.turb(&(frequency * Vector3::new(x, 0.0, z)), 7)
+            })
+            .collect();
+        let (min, max) = heights.iter().fold((f32::MAX, f32::MIN), |(min, max), &h| (min.min(h), max.max(h)));
+        if max > min {
+            heights.iter_mut().for_each(|h| *h = (*h - min) / (max - min));
+        }
+        Heightfield::new(heights, nx, nz, origin, size, material)
+    }
+
+    fn cell_size(&self) -> (f32, f32) {
+        (self.size.x / (self.nx - 1) as f32, self.size.z / (self.nz - 1) as f32)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f32 { self.heights[i + j * self.nx] }
+
+    fn vertex(&self, i: usize, j: usize) -> Vector3<f32> {
+        let (dx, dz) = self.cell_size();
+        self.origin + Vector3::new(i as f32 * dx, self.size.y * self.height(i, j), j as f32 * dz)
+    }
+
+    fn vertex_normal(&self, i: usize, j: usize) -> Vector3<f32> {
+        let (dx, dz) = self.cell_size();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+        let slope_x = self.size.y * (self.height(i1, j) - self.height(i0, j)) / ((i1 - i0) as f32 * dx);
+        let slope_z = self.size.y * (self.height(i, j1) - self.height(i, j0)) / ((j1 - j0) as f32 * dz);
+        Vector3::new(-slope_x, 1.0, -slope_z).normalize()
+    }
+
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest_so_far = t_max;
+        let mut hit_anything = None;
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            let [c0, c1, c2] = triangle.map(|c| corners[c]);
+            let (v0, v1, v2) = (self.vertex(c0.0, c0.1), self.vertex(c1.0, c1.1), self.vertex(c2.0, c2.1));
+            if let Some((t, b1, b2)) = hit_triangle(ray, &v0, &v1, &v2, t_min, closest_so_far) {
+                closest_so_far = t;
+                hit_anything = Some((t, [c0, c1, c2], [1.0 - b1 - b2, b1, b2]));
+            }
+        }
+        hit_anything.map(|(t, vertices, weights)| {
+            let p = ray.point_at_parameter(t);
+            let normal = vertices.iter().zip(weights.iter())
+                .map(|(&(i, j), &w)| w * self.normals[i + j * self.nx])
+                .sum::<Vector3<f32>>()
+                .normalize();
+            // the flat triangle, facing up like the interpolated normal
+            let [v0, v1, v2] = vertices.map(|(i, j)| self.vertex(i, j));
+            let face = (v1 - v0).cross(&(v2 - v0)).normalize();
+            let geometric_normal = if face.y < 0.0 { -face } else { face };
+            let u = (p.x - self.origin.x) / self.size.x;
+            let v = (p.z - self.origin.z) / self.size.z;
+            // v runs along z, against normal x tangent
+            HitRecord { t, u, v, p, normal, geometric_normal, tangent: Vector3::x(), bitangent_sign: -1.0, material: &self.material }
+        })
+    }
+}
+
+impl<M: Material> Hitable for Heightfield<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t_enter, t_exit) = self.bbox.intersect(ray, t_min, t_max)?;
+        let (dx, dz) = self.cell_size();
+        let (cells_x, cells_z) = (self.nx - 1, self.nz - 1);
+        let entry = ray.point_at_parameter(t_enter);
+        let cell = |x: f32, origin: f32, d: f32, cells: usize| (((x - origin) / d).floor().max(0.0) as usize).min(cells - 1);
+        let mut i = cell(entry.x, self.origin.x, dx, cells_x);
+        let mut j = cell(entry.z, self.origin.z, dz, cells_z);
+        // 2D DDA over the grid cells, in the order the ray crosses them
+        let setup = |dir: f32, origin: f32, start: f32, d: f32, index: usize| {
+            if dir > 0.0 {
+                ((start + (index + 1) as f32 * d - origin) / dir, d / dir)
+            } else if dir < 0.0 {
+                ((start + index as f32 * d - origin) / dir, -d / dir)
+            } else {
+                (f32::MAX, f32::MAX)
+            }
+        };
+        let (mut t_next_x, t_delta_x) = setup(ray.direction().x, ray.origin().x, self.origin.x, dx, i);
+        let (mut t_next_z, t_delta_z) = setup(ray.direction().z, ray.origin().z, self.origin.z, dz, j);
+        loop {
+            if let Some(hit) = self.hit_cell(ray, i, j, t_min, t_max) {
+                return Some(hit)
+            }
+            if t_next_x < t_next_z {
+                if t_next_x > t_exit { return None }
+                if ray.direction().x > 0.0 { i += 1 } else if i > 0 { i -= 1 } else { return None }
+                if i >= cells_x { return None }
+                t_next_x += t_delta_x;
+            } else {
+                if t_next_z > t_exit { return None }
+                if ray.direction().z > 0.0 { j += 1 } else if j > 0 { j -= 1 } else { return None }
+                if j >= cells_z { return None }
+                t_next_z += t_delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
@@ -1,21 +1,61 @@
+use std::f32;
 use nalgebra::Vector3;
 use crate::ray::Ray;
 use crate::material::Material;
 use crate::aabb;
 use crate::aabb::AABB;
 
+// moves a ray parameter just past a hit, by an amount that still counts far along the ray
+pub fn step_past(t: f32) -> f32 {
+    t + 0.0001 * f32::max(1.0, t.abs())
+}
+
+#[derive(Clone, Copy)]
 pub struct HitRecord<'a> {
     pub t: f32,
     pub u: f32,
     pub v: f32,
     pub p: Vector3<f32>,
+    // the normal for shading, which interpolation, normal maps and bump maps may bend
     pub normal: Vector3<f32>,
+    // the normal of the surface itself, on the same side as normal. which side a ray is on, and
+    // whether it crosses into a medium, is decided by this one
+    pub geometric_normal: Vector3<f32>,
+    pub tangent: Vector3<f32>,
+    // 1 when normal x tangent runs towards increasing v, -1 when the uv are mirrored against it
+    pub bitangent_sign: f32,
     pub material: &'a dyn Material
 }
 
-pub trait Hitable: Sync {
+pub trait Hitable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB>;
+
+    // the spans of the whole ray line that lie inside a closed hitable, sorted by t and each one
+    // bounded by its entering and exiting hit. By default consecutive surface crossings are paired.
+    fn intervals(&self, ray: &Ray) -> Vec<(HitRecord, HitRecord)> {
+        let mut intervals = Vec::new();
+        let mut t_min = -f32::MAX;
+        while let Some(enter) = self.hit(ray, t_min, f32::MAX) {
+            match self.hit(ray, step_past(enter.t), f32::MAX) {
+                Some(exit) => {
+                    t_min = step_past(exit.t);
+                    intervals.push((enter, exit));
+                },
+                None => break
+            }
+        }
+        intervals
+    }
+}
+
+// a surface that can be sampled uniformly by area, e.g. to use it as a light
+#[allow(dead_code)]
+pub trait Sampleable {
+    fn area(&self) -> f32;
+
+    // a random point on the surface and the surface normal there
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>);
 }
 
 #[derive(Default)]
@@ -72,6 +112,8 @@ impl<H: Hitable> Hitable for FlipNormals<H> {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         self.hitable.hit(&ray, t_min, t_max).map(|mut hit| {
             hit.normal = -hit.normal;
+            hit.geometric_normal = -hit.geometric_normal;
+            hit.bitangent_sign = -hit.bitangent_sign;
             hit
         })
     }
@@ -0,0 +1,49 @@
+use nalgebra::Vector3;
+
+// the medium inside a closed surface. where media overlap, the one of highest priority fills
+// the overlap and the surfaces of the others inside it are ignored
+#[derive(Clone, Copy, PartialEq)]
+pub struct Interior {
+    pub ior: f32,
+    pub priority: u32,
+    // absorption coefficient per unit distance, after Beer's law
+    pub absorption: Vector3<f32>
+}
+
+// the media enclosing a ray, in the order they were entered
+#[derive(Clone, Default)]
+pub struct InteriorStack {
+    media: Vec<Interior>
+}
+
+impl InteriorStack {
+    // the medium the ray travels in, the latest entered of highest priority
+    pub fn current(&self) -> Option<&Interior> {
+        self.media.iter().max_by_key(|m| m.priority)
+    }
+
+    pub fn ior(&self) -> f32 { self.current().map_or(1.0, |m| m.ior) }
+
+    pub fn entered(&self, interior: Interior) -> InteriorStack {
+        let mut media = self.media.clone();
+        media.push(interior);
+        InteriorStack { media }
+    }
+
+    // leaving a medium that was never entered, like from a camera inside it, leaves the stack as is
+    pub fn exited(&self, interior: &Interior) -> InteriorStack {
+        let mut media = self.media.clone();
+        if let Some(i) = media.iter().rposition(|m| m == interior) {
+            media.remove(i);
+        }
+        InteriorStack { media }
+    }
+
+    // fraction of light left after travelling distance through the current medium
+    pub fn transmittance(&self, distance: f32) -> Vector3<f32> {
+        match self.current() {
+            Some(m) => (-distance * m.absorption).map(f32::exp),
+            None => Vector3::new(1.0, 1.0, 1.0)
+        }
+    }
+}
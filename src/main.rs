@@ -1,35 +1,69 @@
 mod ray;
 mod hitable;
 mod medium;
+mod color;
+mod interior;
 mod texture;
 mod perlin;
+mod noise;
+mod procedural;
 mod material;
 mod sphere;
 mod rect;
+mod quad;
+mod curve;
+mod mesh;
+mod subdivision;
+mod voxel;
 mod cube;
+mod torus;
+mod sdf;
+mod quadric;
+mod csg;
+mod heightfield;
 mod translate;
 mod rotate;
+mod transform;
+mod shading;
 mod camera;
 mod aabb;
 mod bvh;
 
 use std::f32;
-use nalgebra::Vector3;
+use std::sync::Arc;
+use nalgebra::{Matrix4, Unit, UnitQuaternion, Vector3};
 use rand::Rng;
-use image;
+use image::{DynamicImage, ImageResult, RgbImage};
 use rayon::prelude::*;
 use crate::ray::Ray;
-use crate::texture::{ConstantTexture, CheckerTexture, NoiseTexture, ImageTexture};
-use crate::material::{Lambertian, Metal, Dielectric, DiffuseLight};
+use crate::texture::{ConstantTexture, CheckerTexture, NoiseTexture, ImageTexture, ColorSpace, Filter, Wrap, TextureCache, MixTexture, MultiplyTexture, AddTexture, RemapTexture, UvTransform, TriplanarTexture};
+use crate::material::{Material, Lambertian, Metal, Dielectric, DiffuseLight, Hair, Isotropic, Emissive, HenyeyGreenstein, DoubleHenyeyGreenstein, Rayleigh};
 use crate::hitable::{Hitable, HitableList, FlipNormals};
-use crate::medium::ConstantMedium;
+use crate::medium::{ConstantMedium, HeterogeneousMedium, DensityGrid, NoiseDensity};
+use crate::interior::InteriorStack;
+use crate::color::{ColorRamp, BlackbodyTexture, RampTexture};
 use crate::sphere::{Sphere, MovingSphere};
 use crate::rect::{AARect, Plane};
+use crate::quad::Quad;
+use crate::curve::Curves;
+use crate::subdivision::ControlCage;
+use crate::voxel::VoxelGrid;
 use crate::cube::Cube;
+use crate::torus::Torus;
+use crate::sdf::SDF;
+use crate::quadric::{Cylinder, Cone, Disk, Paraboloid, Hyperboloid};
+use crate::csg::{CSG, Operation};
+use crate::heightfield::Heightfield;
+use crate::perlin::Perlin;
+use crate::noise::{Simplex, Worley, Feature, Fbm, Ridged, DomainWarp, NoiseFieldTexture};
+use crate::procedural::{Wood, Marble, Brick};
 use crate::translate::Translate;
 use crate::rotate::{Rotate, Axis};
+use crate::transform::{Transform, AnimatedTransform, Keyframe};
+use crate::shading::{BumpMap, NormalMap};
 use crate::camera::Camera;
 use crate::bvh::BVH;
+use crate::aabb::AABB;
 
 #[allow(dead_code)]
 fn random_scene() -> Box<dyn Hitable> {
@@ -75,7 +109,7 @@ fn two_spheres() -> Box<dyn Hitable> {
 
 #[allow(dead_code)]
 fn two_perlin_spheres() -> Box<dyn Hitable> {
-    let noise = NoiseTexture::new(4.0);
+    let noise = NoiseTexture::with_seed(4.0, 1);
     let mut world = HitableList::default();
     world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(noise.clone())));
     world.push(Sphere::new(Vector3::new(0.0, 2.0, 0.0), 2.0, Lambertian::new(noise)));
@@ -83,18 +117,54 @@ fn two_perlin_spheres() -> Box<dyn Hitable> {
 }
 
 #[allow(dead_code)]
-fn earth() -> Box<dyn Hitable> {
-    let image = image::open("earthmap.png").expect("image not found").to_rgb8();
-    let (nx, ny) = image.dimensions();
-    let data = image.into_raw();
-    let texture = ImageTexture::new(data, nx, ny);
+fn earth(textures: &mut TextureCache) -> ImageResult<Box<dyn Hitable>> {
+    let texture = textures.load("earthmap.png", ColorSpace::Srgb)?;
     let earth = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 2.0, Lambertian::new(texture));
-    Box::new(earth)
+    Ok(Box::new(earth))
+}
+
+#[allow(dead_code)]
+fn bumpy_spheres(textures: &mut TextureCache) -> ImageResult<Box<dyn Hitable>> {
+    let noise = NoiseTexture::with_seed(4.0, 1);
+    let texture = textures.load("earthmap.png", ColorSpace::Srgb)?;
+    // the bumps come from the stored values, so the same file is loaded again as linear data
+    let heights = textures.load("earthmap.png", ColorSpace::Linear)?;
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(BumpMap::new(Sphere::new(Vector3::new(0.0, 2.0, -2.5), 2.0, Lambertian::new(ConstantTexture::new(0.8, 0.8, 0.8))), noise, 0.05));
+    world.push(BumpMap::new(Sphere::new(Vector3::new(0.0, 2.0, 2.5), 2.0, Lambertian::new(texture)), heights, 0.02));
+    world.push(Sphere::new(Vector3::new(10.0, 10.0, 0.0), 3.0, DiffuseLight::new(ConstantTexture::new(10.0, 10.0, 10.0))));
+    Ok(Box::new(world))
+}
+
+#[allow(dead_code)]
+fn normal_mapping() -> Box<dyn Hitable> {
+    // a tangent space normal map of round studs, in square cells of 32 texels, so that the studs
+    // come out round on a sphere where u goes around twice as far as v goes up
+    let (nx, ny) = (512, 256);
+    let data = (0..ny)
+        .flat_map(|j| (0..nx).map(move |i| (i, j)))
+        .flat_map(|(i, j)| {
+            let x = ((i as f32 + 0.5) / 32.0).fract() * 2.0 - 1.0;
+            let y = 1.0 - ((j as f32 + 0.5) / 32.0).fract() * 2.0;
+            let r2 = (x * x + y * y) / 0.64;
+            let n = if r2 < 1.0 { Vector3::new(x, y, 0.8 * (1.0 - r2).sqrt()).normalize() } else { Vector3::new(0.0, 0.0, 1.0) };
+            n.iter().map(|c| (255.0 * 0.5 * (c + 1.0)).round() as u8).collect::<Vec<u8>>()
+        })
+        .collect();
+    let image = DynamicImage::ImageRgb8(RgbImage::from_raw(nx, ny, data).unwrap());
+    // normals are data, not colour
+    let normals = ImageTexture::from_image(&image, ColorSpace::Linear).with_filter(Filter::Bilinear);
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(NormalMap::new(Sphere::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Lambertian::new(ConstantTexture::new(0.8, 0.6, 0.2))), normals));
+    world.push(Sphere::new(Vector3::new(3.0, 6.0, -4.0), 2.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    Box::new(world)
 }
 
 #[allow(dead_code)]
 fn simple_light() -> Box<dyn Hitable> {
-    let noise = NoiseTexture::new(4.0);
+    let noise = NoiseTexture::with_seed(4.0, 1);
     let mut world = HitableList::default();
     world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(noise.clone())));
     world.push(Sphere::new(Vector3::new(0.0, 2.0, 0.0), 2.0, Lambertian::new(noise)));
@@ -161,26 +231,414 @@ fn cornell_smoke() -> Box<dyn Hitable> {
     Box::new(world)
 }
 
-fn final_scene() -> Box<dyn Hitable> {
+#[allow(dead_code)]
+fn instances() -> Box<dyn Hitable> {
     let mut rng = rand::thread_rng();
     let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
-    let ground = Lambertian::new(ConstantTexture::new(0.48, 0.83, 0.53));
+    let light = DiffuseLight::new(ConstantTexture::new(15.0, 15.0, 15.0));
     let mut world = HitableList::default();
-    let mut box_list1: Vec<Box<dyn Hitable>> = Vec::new();
-    let nb = 20;
-    for i in 0..nb {
-        for j in 0..20 {
-            let w = 100.0;
-            let x0 = -1000.0 + i as f32 * w;
-            let z0 = -1000.0 + j as f32 * w;
-            let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = 100.0 * (rng.gen::<f32>() + 0.01);
-            let z1 = z0 + w;
-            box_list1.push(Box::new(Cube::new(Vector3::new(x0, y0, z0), Vector3::new(x1, y1, z1), ground.clone())));
-        }
+    world.push(AARect::new(Plane::ZX, 227.0, 332.0, 213.0, 343.0, 554.0, light));
+    world.push(FlipNormals::new(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(FlipNormals::new(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white)));
+    let cube: Arc<dyn Hitable> = Arc::new(Cube::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(0.5, 0.5, 0.5), Lambertian::new(ConstantTexture::new(0.7, 0.3, 0.1))));
+    let mut instances: Vec<Box<dyn Hitable>> = Vec::new();
+    for _ in 0..1000 {
+        let position = Vector3::new(555.0 * rng.gen::<f32>(), 555.0 * rng.gen::<f32>(), 555.0 * rng.gen::<f32>());
+        let axis = Unit::new_normalize(Vector3::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5));
+        let scale = Vector3::new(5.0 + 20.0 * rng.gen::<f32>(), 5.0 + 20.0 * rng.gen::<f32>(), 5.0 + 20.0 * rng.gen::<f32>());
+        let matrix = Matrix4::new_translation(&position) *
+            Matrix4::from_axis_angle(&axis, f32::consts::PI * rng.gen::<f32>()) *
+            Matrix4::new_nonuniform_scaling(&scale);
+        instances.push(Box::new(Transform::new(cube.clone(), matrix)));
+    }
+    world.push(BVH::new(instances, 0.0, 1.0));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn spinning_cubes() -> Box<dyn Hitable> {
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(ConstantTexture::new(15.0, 15.0, 15.0));
+    let mut world = HitableList::default();
+    world.push(FlipNormals::new(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(AARect::new(Plane::ZX, 227.0, 332.0, 213.0, 343.0, 554.0, light));
+    world.push(FlipNormals::new(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(FlipNormals::new(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    let cube: Arc<dyn Hitable> = Arc::new(Cube::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(0.5, 0.5, 0.5), white));
+    let mut cubes: Vec<Box<dyn Hitable>> = Vec::new();
+    let spin = vec![
+        Keyframe::new(0.0, Vector3::new(190.0, 120.0, 150.0), UnitQuaternion::identity(), Vector3::new(165.0, 165.0, 165.0)),
+        Keyframe::new(1.0, Vector3::new(190.0, 120.0, 150.0), UnitQuaternion::from_euler_angles(0.0, 0.5, 0.0), Vector3::new(165.0, 165.0, 165.0))];
+    cubes.push(Box::new(AnimatedTransform::new(cube.clone(), spin)));
+    let slide = vec![
+        Keyframe::new(0.0, Vector3::new(370.0, 165.0, 380.0), UnitQuaternion::from_euler_angles(0.0, 0.26, 0.0), Vector3::new(165.0, 330.0, 165.0)),
+        Keyframe::new(1.0, Vector3::new(400.0, 200.0, 380.0), UnitQuaternion::from_euler_angles(0.0, 0.26, 0.0), Vector3::new(165.0, 330.0, 165.0))];
+    cubes.push(Box::new(AnimatedTransform::new(cube, slide)));
+    world.push(BVH::new(cubes, 0.0, 1.0));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn csg() -> Box<dyn Hitable> {
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(ConstantTexture::new(15.0, 15.0, 15.0));
+    let mut world = HitableList::default();
+    world.push(FlipNormals::new(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green.clone())));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red.clone()));
+    world.push(AARect::new(Plane::ZX, 227.0, 332.0, 213.0, 343.0, 554.0, light));
+    world.push(FlipNormals::new(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(FlipNormals::new(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(CSG::new(Operation::Difference,
+                        Sphere::new(Vector3::new(400.0, 100.0, 300.0), 100.0, white.clone()),
+                        Cube::new(Vector3::new(400.0, 100.0, 150.0), Vector3::new(550.0, 250.0, 300.0), red)));
+    world.push(CSG::new(Operation::Intersection,
+                        Sphere::new(Vector3::new(150.0, 90.0, 200.0), 90.0, white.clone()),
+                        Translate::new(
+                            Rotate::new(Axis::Y,
+                                        Cube::new(Vector3::new(-70.0, -70.0, -70.0), Vector3::new(70.0, 70.0, 70.0), green),
+                                        30.0),
+                            Vector3::new(150.0, 90.0, 200.0))));
+    world.push(CSG::new(Operation::Union,
+                        Sphere::new(Vector3::new(260.0, 300.0, 350.0), 60.0, Dielectric::new(1.5)),
+                        Sphere::new(Vector3::new(320.0, 300.0, 350.0), 60.0, Dielectric::new(1.5))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn quadrics() -> Box<dyn Hitable> {
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(ConstantTexture::new(15.0, 15.0, 15.0));
+    let mut world = HitableList::default();
+    world.push(FlipNormals::new(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(FlipNormals::new(Disk::new(Vector3::new(278.0, 554.0, 278.0), 80.0, 30.0, 360.0, light)));
+    world.push(FlipNormals::new(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(FlipNormals::new(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(Cylinder::new(Vector3::new(420.0, 0.0, 350.0), 70.0, 200.0, 360.0, true, white.clone()));
+    world.push(Cylinder::new(Vector3::new(420.0, 300.0, 350.0), 50.0, 100.0, 270.0, false, Metal::new(Vector3::new(0.8, 0.8, 0.9), 0.1)));
+    world.push(Cone::new(Vector3::new(150.0, 0.0, 250.0), 80.0, 220.0, 360.0, true, white.clone()));
+    world.push(Paraboloid::new(Vector3::new(280.0, 0.0, 120.0), 60.0, 120.0, 300.0, white.clone()));
+    world.push(Hyperboloid::new(Vector3::new(150.0, 300.0, 350.0), 80.0, 40.0, 160.0, 360.0, white));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn sdf_shapes() -> Box<dyn Hitable> {
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(ConstantTexture::new(15.0, 15.0, 15.0));
+    let mut world = HitableList::default();
+    world.push(FlipNormals::new(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(AARect::new(Plane::ZX, 227.0, 332.0, 213.0, 343.0, 554.0, light));
+    world.push(FlipNormals::new(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(FlipNormals::new(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(Torus::new(Vector3::new(400.0, 60.0, 300.0), 100.0, 40.0, Metal::new(Vector3::new(0.8, 0.6, 0.2), 0.2)));
+    let blob = sdf::smooth_union(
+        sdf::sphere(Vector3::new(150.0, 100.0, 250.0), 70.0),
+        sdf::cuboid(Vector3::new(150.0, 40.0, 250.0), Vector3::new(100.0, 40.0, 60.0)),
+        30.0);
+    world.push(SDF::new(blob, AABB::new(Vector3::new(40.0, -10.0, 140.0), Vector3::new(260.0, 180.0, 360.0)), white.clone()));
+    let column = sdf::twist(sdf::cuboid(Vector3::new(300.0, 300.0, 350.0), Vector3::new(40.0, 100.0, 40.0)), Vector3::new(300.0, 300.0, 350.0), 0.01);
+    world.push(SDF::new(column, AABB::new(Vector3::new(240.0, 190.0, 290.0), Vector3::new(360.0, 410.0, 410.0)), white.clone()));
+    let beads = sdf::repeat(sdf::sphere(Vector3::zeros(), 12.0), Vector3::new(40.0, 40.0, 40.0));
+    world.push(SDF::new(beads, AABB::new(Vector3::new(60.0, 380.0, 380.0), Vector3::new(220.0, 420.0, 540.0)), white));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn tilted_panels() -> Box<dyn Hitable> {
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(ConstantTexture::new(15.0, 15.0, 15.0));
+    let mut world = HitableList::default();
+    world.push(Quad::new(Vector3::new(555.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 555.0), Vector3::new(0.0, 555.0, 0.0), green));
+    world.push(Quad::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 555.0, 0.0), Vector3::new(0.0, 0.0, 555.0), red));
+    world.push(Quad::new(Vector3::new(180.0, 520.0, 200.0), Vector3::new(200.0, 30.0, 0.0), Vector3::new(0.0, 0.0, 150.0), light));
+    world.push(Quad::new(Vector3::new(0.0, 555.0, 0.0), Vector3::new(555.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 555.0), white.clone()));
+    world.push(Quad::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 555.0), Vector3::new(555.0, 0.0, 0.0), white.clone()));
+    world.push(Quad::new(Vector3::new(0.0, 0.0, 555.0), Vector3::new(0.0, 555.0, 0.0), Vector3::new(555.0, 0.0, 0.0), white.clone()));
+    world.push(Cube::oriented(Vector3::new(130.0, 0.0, 65.0), Vector3::new(157.0, 0.0, -51.0), Vector3::new(0.0, 165.0, 0.0), Vector3::new(51.0, 0.0, 157.0), white.clone()));
+    world.push(Cube::oriented(Vector3::new(265.0, 0.0, 295.0), Vector3::new(159.0, 30.0, 43.0), Vector3::new(-25.0, 300.0, 0.0), Vector3::new(-43.0, 0.0, 159.0), white));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn crates(textures: &mut TextureCache) -> ImageResult<Box<dyn Hitable>> {
+    let texture = textures.load("earthmap.png", ColorSpace::Srgb)?;
+    let faces = [
+        (0.8, 0.1, 0.1), (0.1, 0.8, 0.1), (0.1, 0.1, 0.8),
+        (0.8, 0.8, 0.1), (0.1, 0.8, 0.8), (0.8, 0.1, 0.8)
+    ].map(|(r, g, b)| Lambertian::new(ConstantTexture::new(r, g, b)));
+    let mut world = HitableList::default();
+    world.push(Translate::new(Rotate::new(Axis::Y, Cube::with_faces(Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 2.0, 1.0), faces), 30.0), Vector3::new(-1.5, 0.0, 0.0)));
+    world.push(Translate::new(Rotate::new(Axis::Y, Cube::cubemap(Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 2.0, 1.0), Lambertian::new(texture)), -30.0), Vector3::new(1.5, 0.0, 0.0)));
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(Sphere::new(Vector3::new(0.0, 10.0, -10.0), 4.0, DiffuseLight::new(ConstantTexture::new(6.0, 6.0, 6.0))));
+    Ok(Box::new(world))
+}
+
+#[allow(dead_code)]
+fn furry_ball() -> Box<dyn Hitable> {
+    let mut rng = rand::thread_rng();
+    let mut hairs = Vec::new();
+    while hairs.len() < 20000 {
+        let p = 2.0 * Vector3::new(rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>()) - Vector3::new(1.0, 1.0, 1.0);
+        if p.norm_squared() > 1.0 || p.norm_squared() < 0.01 { continue }
+        let n = p.normalize();
+        let root = Vector3::new(0.0, 1.0, 0.0) + n;
+        let gravity = Vector3::new(0.0, -0.15, 0.0);
+        hairs.push([root, root + 0.15 * n, root + 0.3 * n + 0.5 * gravity, root + 0.4 * n + gravity]);
     }
-    world.push(BVH::new(box_list1, 0.0, 1.0));
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Lambertian::new(ConstantTexture::new(0.3, 0.15, 0.05))));
+    world.push(Curves::new(hairs, 0.01, 0.002, Hair::new(Vector3::new(0.6, 0.35, 0.15), 0.3, 0.3)));
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(Sphere::new(Vector3::new(-4.0, 8.0, -6.0), 3.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn subdivision_surfaces() -> Box<dyn Hitable> {
+    let corners = (0..8)
+        .map(|i| Vector3::new(if i & 1 == 0 { -1.0 } else { 1.0 }, if i & 2 == 0 { -1.0 } else { 1.0 }, if i & 4 == 0 { -1.0 } else { 1.0 }))
+        .collect();
+    let cube = ControlCage::new(corners, vec![
+        vec![0, 2, 3, 1], vec![4, 5, 7, 6], vec![0, 4, 6, 2],
+        vec![1, 3, 7, 5], vec![0, 1, 5, 4], vec![2, 6, 7, 3]]);
+    let octahedron = ControlCage::new(
+        vec![Vector3::x(), -Vector3::x(), Vector3::y(), -Vector3::y(), Vector3::z(), -Vector3::z()],
+        vec![
+            vec![0, 2, 4], vec![2, 1, 4], vec![1, 3, 4], vec![3, 0, 4],
+            vec![2, 0, 5], vec![1, 2, 5], vec![3, 1, 5], vec![0, 3, 5]]);
+    let mut world = HitableList::default();
+    world.push(Translate::new(cube.catmull_clark(3).to_mesh(Lambertian::new(ConstantTexture::new(0.8, 0.3, 0.1))), Vector3::new(-1.5, 1.0, 0.0)));
+    world.push(Translate::new(octahedron.loop_subdivision(3).to_mesh(Metal::new(Vector3::new(0.8, 0.8, 0.9), 0.1)), Vector3::new(1.5, 1.0, 0.0)));
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(Sphere::new(Vector3::new(-4.0, 8.0, -6.0), 3.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn voxel_world() -> Box<dyn Hitable> {
+    let noise = Perlin::with_seed(1);
+    let materials: Vec<Box<dyn Material>> = vec![
+        Box::new(Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))),
+        Box::new(Lambertian::new(ConstantTexture::new(0.45, 0.3, 0.15))),
+        Box::new(Lambertian::new(ConstantTexture::new(0.2, 0.6, 0.1))),
+        Box::new(DiffuseLight::new(ConstantTexture::new(2.0, 0.5, 0.1)))
+    ];
+    // stone below, then dirt and a layer of grass on top, with lava glowing in the low ground
+    let grid = VoxelGrid::from_fn([48, 24, 48], Vector3::new(-24.0, 0.0, -24.0), 1.0, materials, |i, j, k| {
+        let height = (4.0 + 16.0 * noise.turb(&Vector3::new(i as f32 * 0.08, 0.0, k as f32 * 0.08), 4)) as usize;
+        if j > height { None } else if j == height { Some(if height < 5 { 3 } else { 2 }) } else if j + 3 > height { Some(1) } else { Some(0) }
+    });
+    let mut world = HitableList::default();
+    world.push(grid);
+    world.push(Sphere::new(Vector3::new(-30.0, 80.0, -40.0), 40.0, DiffuseLight::new(ConstantTexture::new(4.0, 4.0, 4.0))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn cornell_clouds() -> Box<dyn Hitable> {
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(ConstantTexture::new(7.0, 7.0, 7.0));
+    let mut world = HitableList::default();
+    world.push(FlipNormals::new(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(AARect::new(Plane::ZX, 127.0, 432.0, 113.0, 443.0, 554.0, light));
+    world.push(FlipNormals::new(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    world.push(AARect::new(Plane::ZX, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(FlipNormals::new(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone())));
+    // a cloud of turbulence in a sphere
+    let cloud = NoiseDensity::new(Perlin::with_seed(1), 0.02, 0.05);
+    let boundary = Sphere::new(Vector3::new(400.0, 350.0, 280.0), 120.0, white.clone());
+    world.push(HeterogeneousMedium::with_phase_function(boundary, cloud, DoubleHenyeyGreenstein::new(ConstantTexture::new(1.0, 1.0, 1.0), 0.8, -0.3, 0.9)));
+    // a plume of smoke thinning out as it rises and spreads
+    let n = 32;
+    let plume = (0..n * n * n)
+        .map(|i| (i % n, (i / n) % n, i / (n * n)))
+        .map(|(i, j, k)| {
+            let height = j as f32 / (n - 1) as f32;
+            let radius = 0.1 + 0.3 * height;
+            let x = i as f32 / (n - 1) as f32 - 0.5;
+            let z = k as f32 / (n - 1) as f32 - 0.5;
+            0.05 * (1.0 - height) * (-(x * x + z * z) / (radius * radius)).exp()
+        })
+        .collect();
+    let plume = DensityGrid::new(plume, [n, n, n], Vector3::new(50.0, 0.0, 150.0), Vector3::new(300.0, 500.0, 300.0));
+    let boundary = Cube::new(Vector3::new(50.0, 0.0, 150.0), Vector3::new(350.0, 500.0, 450.0), white);
+    world.push(HeterogeneousMedium::with_phase_function(boundary, plume, HenyeyGreenstein::new(ConstantTexture::new(0.2, 0.2, 0.2), 0.3)));
+    // a faint haze filling the box
+    let boundary = Cube::new(Vector3::new(0.1, 0.1, 0.1), Vector3::new(554.9, 553.9, 554.9), Lambertian::new(ConstantTexture::new(0.0, 0.0, 0.0)));
+    world.push(ConstantMedium::with_phase_function(boundary, 0.0005, Rayleigh::new(ConstantTexture::new(0.6, 0.8, 1.0))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn smoke_ring() -> Box<dyn Hitable> {
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(Sphere::new(Vector3::new(-4.0, 8.0, -6.0), 3.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    // a ring of smoke, the ray may cross it twice
+    let ring = Torus::new(Vector3::new(0.0, 1.5, 0.0), 1.2, 0.5, Lambertian::new(ConstantTexture::new(0.0, 0.0, 0.0)));
+    world.push(ConstantMedium::with_phase_function(ring, 2.0, HenyeyGreenstein::new(ConstantTexture::new(0.9, 0.5, 0.3), 0.5)));
+    // thin fog around the camera as well as the scene
+    let fog = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 30.0, Lambertian::new(ConstantTexture::new(0.0, 0.0, 0.0)));
+    world.push(ConstantMedium::new(fog, 0.02, ConstantTexture::new(0.8, 0.8, 0.8)));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn fireball() -> Box<dyn Hitable> {
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    // hottest in the core, cooling towards the edge
+    let n = 32;
+    let temperature = (0..n * n * n)
+        .map(|i| Vector3::new((i % n) as f32, ((i / n) % n) as f32, (i / (n * n)) as f32) / (n - 1) as f32 - Vector3::new(0.5, 0.5, 0.5))
+        .map(|p| 2500.0 * (1.0 - 2.0 * p.norm()).max(0.0).sqrt())
+        .collect();
+    let temperature = DensityGrid::new(temperature, [n, n, n], Vector3::new(-1.5, 0.0, -1.5), Vector3::new(3.0, 3.0, 3.0));
+    let fire = Emissive::new(Isotropic::new(ConstantTexture::new(0.2, 0.2, 0.2)), BlackbodyTexture::new(temperature, 0.2));
+    let boundary = Sphere::new(Vector3::new(0.0, 1.5, 0.0), 1.5, Lambertian::new(ConstantTexture::new(0.0, 0.0, 0.0)));
+    world.push(HeterogeneousMedium::with_phase_function(boundary, NoiseDensity::new(Perlin::with_seed(1), 2.0, 2.0), fire));
+    // glowing gas coloured by its own density
+    let ramp = ColorRamp::new(vec![
+        (0.0, Vector3::new(0.0, 0.0, 0.0)),
+        (0.5, Vector3::new(0.1, 0.8, 0.3)),
+        (1.5, Vector3::new(0.8, 1.0, 0.9))]);
+    // the colour follows the density, as both come from the same seed
+    let glow = Emissive::new(Isotropic::new(ConstantTexture::new(0.5, 0.5, 0.5)), RampTexture::new(NoiseDensity::new(Perlin::with_seed(2), 3.0, 1.0), ramp, 0.5));
+    let boundary = Sphere::new(Vector3::new(3.0, 1.0, 1.0), 1.0, Lambertian::new(ConstantTexture::new(0.0, 0.0, 0.0)));
+    world.push(HeterogeneousMedium::with_phase_function(boundary, NoiseDensity::new(Perlin::with_seed(2), 3.0, 1.0), glow));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn glass_of_water() -> Box<dyn Hitable> {
+    let glass = Dielectric::nested(1.5, 3, Vector3::zeros());
+    let water = Dielectric::nested(1.33, 1, Vector3::new(0.3, 0.08, 0.05));
+    let ice = Dielectric::nested(1.31, 2, Vector3::new(0.02, 0.02, 0.01));
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(CheckerTexture::new(ConstantTexture::new(0.2, 0.3, 0.1), ConstantTexture::new(0.9, 0.9, 0.9)))));
+    world.push(Sphere::new(Vector3::new(-4.0, 8.0, -6.0), 3.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    world.push(CSG::new(Operation::Difference,
+        Cylinder::new(Vector3::new(0.0, 0.0, 0.0), 1.0, 2.5, 360.0, true, glass.clone()),
+        Cylinder::new(Vector3::new(0.0, 0.15, 0.0), 0.9, 2.5, 360.0, true, glass)));
+    // the water overlaps the glass, which wins there, so the surface between them is water to glass
+    world.push(Cylinder::new(Vector3::new(0.0, 0.1, 0.0), 0.95, 1.6, 360.0, true, water));
+    world.push(Cube::oriented(Vector3::new(-0.3, 1.35, -0.2), Vector3::new(0.5, 0.1, 0.1), Vector3::new(-0.1, 0.5, 0.0), Vector3::new(0.0, -0.1, 0.5), ice));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn texture_filtering(textures: &mut TextureCache) -> ImageResult<Box<dyn Hitable>> {
+    let earth = textures.load("earthmap.png", ColorSpace::Srgb)?;
+    let tiles = earth.clone()
+        .with_filter(Filter::Bilinear)
+        .with_wrap(Wrap::Mirror)
+        .with_uv_transform((4.0, 4.0), (0.0, 0.0));
+    let blurred = earth.with_filter(Filter::Prefiltered(4.5)).with_wrap(Wrap::Repeat);
+    let mut world = HitableList::default();
+    world.push(Quad::new(Vector3::new(-10.0, 0.0, -10.0), Vector3::new(0.0, 0.0, 20.0), Vector3::new(20.0, 0.0, 0.0), Lambertian::new(tiles)));
+    world.push(Sphere::new(Vector3::new(0.0, 1.5, 0.0), 1.5, Lambertian::new(blurred)));
+    world.push(Sphere::new(Vector3::new(-4.0, 8.0, -6.0), 3.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    Ok(Box::new(world))
+}
+
+#[allow(dead_code)]
+fn hdr_sky(textures: &mut TextureCache) -> ImageResult<Box<dyn Hitable>> {
+    let sky = textures.load("sky.hdr", ColorSpace::Linear)?.with_filter(Filter::Bilinear);
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 100.0, DiffuseLight::new(sky)));
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(Sphere::new(Vector3::new(-1.2, 1.0, 0.0), 1.0, Metal::new(Vector3::new(0.9, 0.9, 0.9), 0.0)));
+    world.push(Sphere::new(Vector3::new(1.2, 1.0, 0.0), 1.0, Lambertian::new(ConstantTexture::new(0.8, 0.3, 0.1))));
+    Ok(Box::new(world))
+}
+
+#[allow(dead_code)]
+fn noise_gallery() -> Box<dyn Hitable> {
+    // panels facing a camera on -z, the first one top left
+    let (up, left) = (Vector3::new(0.0, 2.0, 0.0), Vector3::new(-2.0, 0.0, 0.0));
+    let corner = |column: usize, row: usize| Vector3::new(3.2 - 2.2 * column as f32, 0.1 - 2.2 * row as f32, 0.0);
+    let mut world = HitableList::default();
+    world.push(Quad::new(corner(0, 0), up, left, DiffuseLight::new(NoiseFieldTexture::new(Fbm::new(Perlin::with_seed(1), 6, 2.0, 0.5), 1.5))));
+    world.push(Quad::new(corner(1, 0), up, left, DiffuseLight::new(NoiseFieldTexture::new(Ridged::new(Perlin::with_seed(1), 6, 2.0, 0.5), 2.0))));
+    world.push(Quad::new(corner(2, 0), up, left, DiffuseLight::new(NoiseFieldTexture::new(Simplex::new(1), 2.0))));
+    world.push(Quad::new(corner(0, 1), up, left, DiffuseLight::new(NoiseFieldTexture::new(Worley::new(1, Feature::F1), 3.0))));
+    world.push(Quad::new(corner(1, 1), up, left, DiffuseLight::new(NoiseFieldTexture::new(Worley::new(1, Feature::Edges), 3.0))));
+    let warp = Fbm::new(Simplex::new(2), 4, 2.0, 0.5);
+    world.push(Quad::new(corner(2, 1), up, left, DiffuseLight::new(NoiseFieldTexture::new(DomainWarp::new(Fbm::new(Simplex::new(1), 4, 2.0, 0.5), warp, 0.8), 1.0))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn procedural_textures() -> Box<dyn Hitable> {
+    let wood = Wood::new(Perlin::with_seed(1), 4.0, Vector3::new(0.75, 0.55, 0.3), Vector3::new(0.4, 0.22, 0.1));
+    let marble = Marble::new(Fbm::new(Perlin::with_seed(2), 7, 2.0, 0.5), 4.0, Vector3::new(0.9, 0.9, 0.88), Vector3::new(0.2, 0.25, 0.3));
+    // stops in the raw values of the noise, which five octaves spread over about [-1.9, 1.9]
+    let sunset = ColorRamp::new(vec![
+        (-0.8, Vector3::new(0.1, 0.05, 0.3)),
+        (0.0, Vector3::new(0.8, 0.2, 0.3)),
+        (0.8, Vector3::new(1.0, 0.8, 0.3))]);
+    let gradient = RampTexture::new(Fbm::new(Simplex::new(3), 5, 2.0, 0.5), sunset, 1.0);
+    let bricks = Brick::new(12.0, Vector3::new(0.6, 0.25, 0.15), Vector3::new(0.7, 0.7, 0.65));
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(2.5, 1.0, 0.0), 1.0, Lambertian::new(wood)));
+    world.push(Sphere::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Lambertian::new(marble)));
+    world.push(Sphere::new(Vector3::new(-2.5, 1.0, 0.0), 1.0, Lambertian::new(gradient)));
+    // a square wall keeps the bricks twice as wide as high
+    world.push(Quad::new(Vector3::new(3.0, 0.0, 2.0), Vector3::new(-6.0, 0.0, 0.0), Vector3::new(0.0, 6.0, 0.0), Lambertian::new(bricks)));
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))));
+    world.push(Sphere::new(Vector3::new(0.0, 8.0, -6.0), 2.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    Box::new(world)
+}
+
+#[allow(dead_code)]
+fn texture_combinators() -> Box<dyn Hitable> {
+    let bricks = Brick::new(2.0, Vector3::new(0.6, 0.25, 0.15), Vector3::new(0.7, 0.7, 0.65));
+    // bricks laid by position, where the uv of a sphere would pinch at the poles
+    let triplanar = TriplanarTexture::new(bricks.clone(), 2.0, 4.0);
+    // two colours split along a sharpened band of noise
+    let split = RemapTexture::new(NoiseFieldTexture::new(Fbm::new(Simplex::new(1), 5, 2.0, 0.5), 2.0), (0.45, 0.55), (0.0, 1.0));
+    let mix = MixTexture::new(ConstantTexture::new(0.8, 0.2, 0.1), ConstantTexture::new(0.1, 0.3, 0.8), split);
+    let tinted = MultiplyTexture::new(UvTransform::new(bricks, (16.0, 8.0), 45.0, (0.0, 0.0)), ConstantTexture::new(0.5, 1.0, 0.8));
+    let cells = MultiplyTexture::new(NoiseFieldTexture::new(Worley::new(1, Feature::Edges), 1.0), ConstantTexture::new(0.3, 0.3, 0.3));
+    let ground = AddTexture::new(ConstantTexture::new(0.2, 0.2, 0.2), cells);
+    let mut world = HitableList::default();
+    world.push(Sphere::new(Vector3::new(2.5, 1.0, 0.0), 1.0, Lambertian::new(triplanar)));
+    world.push(Sphere::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Lambertian::new(mix)));
+    world.push(Sphere::new(Vector3::new(-2.5, 1.0, 0.0), 1.0, Lambertian::new(tinted)));
+    world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Lambertian::new(ground)));
+    world.push(Sphere::new(Vector3::new(0.0, 8.0, -6.0), 3.0, DiffuseLight::new(ConstantTexture::new(8.0, 8.0, 8.0))));
+    Box::new(world)
+}
+
+fn final_scene(textures: &mut TextureCache) -> ImageResult<Box<dyn Hitable>> {
+    let mut rng = rand::thread_rng();
+    let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let ground = Lambertian::new(ConstantTexture::new(0.48, 0.83, 0.53));
+    let mut world = HitableList::default();
+    // rolling hills, low enough to stay under the spheres
+    world.push(Heightfield::from_noise(&Perlin::with_seed(1), 257, 257, 0.004, Vector3::new(-1000.0, 0.0, -1000.0), Vector3::new(2000.0, 100.0, 2000.0), ground));
     let light = DiffuseLight::new(ConstantTexture::new(7.0, 7.0, 7.0));
     world.push(AARect::new(Plane::ZX, 147.0, 412.0, 123.0, 423.0, 554.0, light));
     let center = Vector3::new(400.0, 400.0, 200.0);
@@ -192,12 +650,9 @@ fn final_scene() -> Box<dyn Hitable> {
     world.push(ConstantMedium::new(boundary, 0.2, ConstantTexture::new(0.2, 0.4, 0.9)));
     let boundary = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 5000.0, Dielectric::new(1.5));
     world.push(ConstantMedium::new(boundary, 0.0001, ConstantTexture::new(1.0, 1.0, 1.0)));
-    let image = image::open("earthmap.png").expect("image not found").to_rgb8();
-    let (nx, ny) = image.dimensions();
-    let data = image.into_raw();
-    let texture = ImageTexture::new(data, nx, ny);
+    let texture = textures.load("earthmap.png", ColorSpace::Srgb)?;
     world.push(Sphere::new(Vector3::new(400.0, 200.0, 400.0), 100.0, Lambertian::new(texture)));
-    world.push(Sphere::new(Vector3::new(220.0, 280.0, 300.0), 80.0, Lambertian::new(NoiseTexture::new(0.1))));
+    world.push(Sphere::new(Vector3::new(220.0, 280.0, 300.0), 80.0, Lambertian::new(NoiseTexture::with_seed(0.1, 1))));
     let mut box_list2: Vec<Box<dyn Hitable>> = Vec::new();
     let ns = 1000;
     for _ in 0..ns {
@@ -210,29 +665,47 @@ fn final_scene() -> Box<dyn Hitable> {
                         15.0),
             Vector3::new(-100.0, 270.0, 395.0))
     );
-    Box::new(world)
+    Ok(Box::new(world))
 }
 
-fn color(ray: &Ray, world: &Box<dyn Hitable>, depth: i32) -> Vector3<f32> {
+fn color(ray: &Ray, world: &Box<dyn Hitable>, depth: i32, interior: &InteriorStack) -> Vector3<f32> {
     if let Some(hit) = world.hit(ray, 0.001, f32::MAX) {
-        let emitted = hit.material.emitted(hit.u, hit.v, &hit.p);
+        // light is absorbed on its way through the medium the ray is in
+        let transmittance = interior.transmittance(hit.t * ray.direction().norm());
+        let emitted = hit.material.emitted(&hit);
         if depth < 50 {
-            if let Some((scattered, attenuation)) = hit.material.scatter(&ray, &hit) {
-                return emitted + attenuation.zip_map(&color(&scattered, &world, depth+1), |l, r| l * r);
+            if let Some(medium) = hit.material.interior() {
+                let entering = ray.direction().dot(&hit.geometric_normal) < 0.0;
+                let beyond = if entering { interior.entered(medium) } else { interior.exited(&medium) };
+                // the media on the other side of the surface, leaving the one it bounds out
+                let outside = if entering { interior } else { &beyond };
+                if outside.current().is_some_and(|m| m.priority > medium.priority) {
+                    // the surface lies inside a medium of higher priority, so it is not there
+                    let continued = Ray::new(hit.p, ray.direction(), ray.time());
+                    return transmittance.component_mul(&color(&continued, world, depth+1, &beyond));
+                }
+                if let Some((scattered, attenuation)) = hit.material.scatter_between(ray, &hit, outside.ior()) {
+                    let through = scattered.direction().dot(&hit.geometric_normal) * ray.direction().dot(&hit.geometric_normal) > 0.0;
+                    let next = if through { &beyond } else { interior };
+                    return transmittance.component_mul(&(emitted + attenuation.zip_map(&color(&scattered, world, depth+1, next), |l, r| l * r)));
+                }
+            } else if let Some((scattered, attenuation)) = hit.material.scatter(ray, &hit) {
+                return transmittance.component_mul(&(emitted + attenuation.zip_map(&color(&scattered, world, depth+1, interior), |l, r| l * r)));
             }
         }
-        emitted
+        transmittance.component_mul(&emitted)
     } else {
         Vector3::zeros()
     }
 }
 
-fn main() {
+fn main() -> ImageResult<()> {
     let nx = 800;
     let ny = 800;
     let ns = 100;
     println!("P3\n{} {}\n255", nx, ny);
-    let world = final_scene();
+    let mut textures = TextureCache::default();
+    let world = final_scene(&mut textures)?;
     let look_from = Vector3::new(478.0, 278.0, -600.0);
     let look_at = Vector3::new(278.0, 278.0, 0.0);
     let focus_dist = 10.0;
@@ -249,14 +722,15 @@ fn main() {
                         let u = (x as f32 + rng.gen::<f32>()) / nx as f32;
                         let v = (y as f32 + rng.gen::<f32>()) / ny as f32;
                         let ray = cam.get_ray(u, v);
-                        color(&ray, &world, 0)
+                        color(&ray, &world, 0, &InteriorStack::default())
                     }).sum();
                     col.iter().map(|c|
-                        (255.99 * (c / ns as f32).sqrt().max(0.0).min(1.0)) as u8
+                        (255.99 * color::linear_to_srgb((c / ns as f32).clamp(0.0, 1.0))) as u8
                     ).collect::<Vec<u8>>()
                 }).collect::<Vec<u8>>()
             ).collect::<Vec<u8>>();
     for col in image.chunks(3) {
        println!("{} {} {}", col[0], col[1], col[2]);
     }
+    Ok(())
 }
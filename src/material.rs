@@ -3,6 +3,7 @@ use rand::Rng;
 use crate::ray::Ray;
 use crate::hitable::HitRecord;
 use crate::texture::Texture;
+use crate::interior::Interior;
 
 fn random_in_unit_sphere() -> Vector3<f32> {
     let mut rng = rand::thread_rng();
@@ -36,10 +37,19 @@ fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     r0 + (1.0 -r0) * (1.0 - cosine).powi(5)
 }
 
-pub trait Material: Sync {
+pub trait Material: Send + Sync {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)>;
 
-    fn emitted(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32>;
+    // the light given off at a hit, looked up with the whole hit like the albedo
+    fn emitted(&self, hit: &HitRecord) -> Vector3<f32>;
+
+    // the medium inside closed surfaces of this material, for media nested in each other
+    fn interior(&self) -> Option<Interior> { None }
+
+    // scattering off a surface with a medium of index of refraction outside_ior on its other side
+    fn scatter_between(&self, ray: &Ray, hit: &HitRecord, _outside_ior: f32) -> Option<(Ray, Vector3<f32>)> {
+        self.scatter(ray, hit)
+    }
 }
 
 #[derive(Clone)]
@@ -53,12 +63,15 @@ impl<T: Texture> Lambertian<T> {
 
 impl<T: Texture> Material for Lambertian<T> {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
-        let target = hit.p + hit.normal + random_in_unit_sphere();
-        let scattered = Ray::new(hit.p, target - hit.p, ray.time());
-        Some((scattered, self.albedo.value(hit.u, hit.v, &hit.p)))
+        let direction = hit.normal + random_in_unit_sphere();
+        // a bent shading normal can point the bounce into the surface, it is mirrored back out
+        let below = direction.dot(&hit.geometric_normal);
+        let direction = if below < 0.0 { direction - 2.0 * below * hit.geometric_normal } else { direction };
+        let scattered = Ray::new(hit.p, direction, ray.time());
+        Some((scattered, self.albedo.sample(hit)))
     }
 
-    fn emitted(&self, _u: f32, _v: f32, _p: &Vector3<f32>) -> Vector3<f32> { Vector3::zeros() }
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
 }
 
 #[derive(Clone)]
@@ -77,7 +90,8 @@ impl Material for Metal {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
         let mut reflected = reflect(&ray.direction().normalize(), &hit.normal);
         if self.fuzz > 0.0 { reflected += self.fuzz * random_in_unit_sphere() };
-        if reflected.dot(&hit.normal) > 0.0 {
+        // absorbed when it would leave below the surface itself
+        if reflected.dot(&hit.geometric_normal) > 0.0 {
             let scattered = Ray::new(hit.p, reflected, ray.time());
             Some((scattered, self.albedo))
         } else {
@@ -85,30 +99,50 @@ impl Material for Metal {
         }
     }
 
-    fn emitted(&self, _u: f32, _v: f32, _p: &Vector3<f32>) -> Vector3<f32> { Vector3::zeros() }
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
 }
 
 #[derive(Clone)]
 pub struct Dielectric {
-    ref_idx: f32
+    ref_idx: f32,
+    priority: u32,
+    absorption: Vector3<f32>
 }
 
 impl Dielectric {
-    pub fn new(ref_idx: f32) -> Self { Dielectric { ref_idx } }
+    pub fn new(ref_idx: f32) -> Self { Dielectric::nested(ref_idx, 0, Vector3::zeros()) }
+
+    // a dielectric that can overlap others, the one of highest priority fills the overlap.
+    // light travelling through it is absorbed by absorption per unit distance
+    pub fn nested(ref_idx: f32, priority: u32, absorption: Vector3<f32>) -> Self {
+        Dielectric { ref_idx, priority, absorption }
+    }
 }
 
 impl Material for Dielectric {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+        self.scatter_between(ray, hit, 1.0)
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior { ior: self.ref_idx, priority: self.priority, absorption: self.absorption })
+    }
+
+    fn scatter_between(&self, ray: &Ray, hit: &HitRecord, outside_ior: f32) -> Option<(Ray, Vector3<f32>)> {
         let attenuation = Vector3::new(1.0, 1.0, 1.0);
-        let (outward_normal, ni_over_nt, cosine) = if ray.direction().dot(&hit.normal) > 0.0 {
-            let cosine = self.ref_idx * ray.direction().dot(&hit.normal) / ray.direction().magnitude();
-            (-hit.normal, self.ref_idx, cosine)
+        let ref_idx = self.ref_idx / outside_ior;
+        // the side is that of the surface itself, so that it agrees with the media crossed
+        let (outward_normal, ni_over_nt, cosine) = if ray.direction().dot(&hit.geometric_normal) > 0.0 {
+            let cosine = ref_idx * ray.direction().dot(&hit.normal) / ray.direction().magnitude();
+            (-hit.normal, ref_idx, cosine.max(0.0))
         } else {
             let cosine = -ray.direction().dot(&hit.normal) / ray.direction().magnitude();
-            (hit.normal, 1.0 / self.ref_idx, cosine)
+            (hit.normal, 1.0 / ref_idx, cosine.max(0.0))
         };
         if let Some(refracted) = refract(&ray.direction(), &outward_normal, ni_over_nt) {
-            let reflect_prob = schlick(cosine, self.ref_idx);
+            let reflect_prob = schlick(cosine, ref_idx);
             if rand::thread_rng().gen::<f32>() >= reflect_prob {
                 let scattered = Ray::new(hit.p, refracted, ray.time());
                 return Some((scattered, attenuation))
@@ -118,8 +152,6 @@ impl Material for Dielectric {
         let scattered = Ray::new(hit.p, reflected, ray.time());
         Some((scattered, attenuation))
     }
-
-    fn emitted(&self, _u: f32, _v: f32, _p: &Vector3<f32>) -> Vector3<f32> { Vector3::zeros() }
 }
 
 #[derive(Clone)]
@@ -134,8 +166,8 @@ impl<T: Texture> DiffuseLight<T> {
 impl<T: Texture> Material for DiffuseLight<T> {
     fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> { None }
 
-    fn emitted(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32> {
-        self.emit.value(u, v, &p)
+    fn emitted(&self, hit: &HitRecord) -> Vector3<f32> {
+        self.emit.sample(hit)
     }
 }
 
@@ -151,8 +183,221 @@ impl<T: Texture> Isotropic<T> {
 impl<T: Texture> Material for Isotropic<T> {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
         let scattered = Ray::new(hit.p, random_in_unit_sphere(), ray.time());
-        Some((scattered, self.albedo.value(hit.u, hit.v, &hit.p)))
+        Some((scattered, self.albedo.sample(hit)))
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
+}
+
+// another material glowing with the emission of a texture. as the phase function of a medium,
+// every collision in it adds the emission at that point, so fire and hot gas light the scene
+#[derive(Clone)]
+pub struct Emissive<M: Material, T: Texture> {
+    material: M,
+    emit: T
+}
+
+impl<M: Material, T: Texture> Emissive<M, T> {
+    pub fn new(material: M, emit: T) -> Self { Emissive { material, emit } }
+}
+
+impl<M: Material, T: Texture> Material for Emissive<M, T> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+        self.material.scatter(ray, hit)
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Vector3<f32> {
+        self.material.emitted(hit) + self.emit.sample(hit)
+    }
+
+    fn interior(&self) -> Option<Interior> { self.material.interior() }
+
+    fn scatter_between(&self, ray: &Ray, hit: &HitRecord, outside_ior: f32) -> Option<(Ray, Vector3<f32>)> {
+        self.material.scatter_between(ray, hit, outside_ior)
+    }
+}
+
+// a unit vector at angle acos(cos_theta) to axis, at a uniformly random azimuth around it
+fn random_around(axis: &Vector3<f32>, cos_theta: f32) -> Vector3<f32> {
+    let w = axis.normalize();
+    let a = if w.x.abs() > 0.9 { Vector3::y() } else { Vector3::x() };
+    let v = w.cross(&a).normalize();
+    let u = w.cross(&v);
+    let phi = 2.0 * std::f32::consts::PI * rand::thread_rng().gen::<f32>();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w
+}
+
+// cosine of the angle to the direction of travel drawn from the Henyey-Greenstein distribution
+fn henyey_greenstein_cos(g: f32) -> f32 {
+    let xi = rand::thread_rng().gen::<f32>();
+    if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    }
+}
+
+// phase function of Henyey and Greenstein, scattering forward for g > 0 and backward for g < 0
+#[derive(Clone)]
+pub struct HenyeyGreenstein<T: Texture> {
+    albedo: T,
+    g: f32
+}
+
+impl<T: Texture> HenyeyGreenstein<T> {
+    pub fn new(albedo: T, g: f32) -> Self { HenyeyGreenstein { albedo, g: g.clamp(-0.999, 0.999) } }
+}
+
+impl<T: Texture> Material for HenyeyGreenstein<T> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+        let direction = random_around(&ray.direction(), henyey_greenstein_cos(self.g));
+        let scattered = Ray::new(hit.p, direction, ray.time());
+        Some((scattered, self.albedo.sample(hit)))
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
+}
+
+// a blend of a forward and a backward Henyey-Greenstein lobe, weight going to the first,
+// like the strong forward peak and the glory of clouds
+#[derive(Clone)]
+pub struct DoubleHenyeyGreenstein<T: Texture> {
+    albedo: T,
+    g1: f32,
+    g2: f32,
+    weight: f32
+}
+
+impl<T: Texture> DoubleHenyeyGreenstein<T> {
+    pub fn new(albedo: T, g1: f32, g2: f32, weight: f32) -> Self {
+        DoubleHenyeyGreenstein { albedo, g1: g1.clamp(-0.999, 0.999), g2: g2.clamp(-0.999, 0.999), weight: weight.clamp(0.0, 1.0) }
+    }
+}
+
+impl<T: Texture> Material for DoubleHenyeyGreenstein<T> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+        let g = if rand::thread_rng().gen::<f32>() < self.weight { self.g1 } else { self.g2 };
+        let direction = random_around(&ray.direction(), henyey_greenstein_cos(g));
+        let scattered = Ray::new(hit.p, direction, ray.time());
+        Some((scattered, self.albedo.sample(hit)))
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
+}
+
+// phase function of particles much smaller than the wavelength, like the molecules of air,
+// proportional to 1 + cos^2
+#[derive(Clone)]
+pub struct Rayleigh<T: Texture> {
+    albedo: T
+}
+
+impl<T: Texture> Rayleigh<T> {
+    pub fn new(albedo: T) -> Self { Rayleigh { albedo } }
+}
+
+impl<T: Texture> Material for Rayleigh<T> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+        // inverts the cumulative distribution, a cubic in the cosine
+        let xi = 2.0 * rand::thread_rng().gen::<f32>() - 1.0;
+        let u = -(2.0 * xi + (4.0 * xi * xi + 1.0).sqrt()).cbrt();
+        let cos_theta = (u - 1.0 / u).clamp(-1.0, 1.0);
+        let direction = random_around(&ray.direction(), cos_theta);
+        let scattered = Ray::new(hit.p, direction, ray.time());
+        Some((scattered, self.albedo.sample(hit)))
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
+}
+
+// a standard normal sample, by the Box-Muller transform
+fn random_normal() -> f32 {
+    let mut rng = rand::thread_rng();
+    let u1 = 1.0 - rng.gen::<f32>();
+    let u2 = rng.gen::<f32>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
+// a logistic sample with scale s, trimmed to [-pi, pi]
+fn random_logistic(s: f32) -> f32 {
+    let mut rng = rand::thread_rng();
+    loop {
+        let u = rng.gen::<f32>().clamp(1e-6, 1.0 - 1e-6);
+        let x = -s * (1.0 / u - 1.0).ln();
+        if x.abs() <= std::f32::consts::PI {
+            return x
+        }
+    }
+}
+
+// a hair fiber after Marschner et al., with the R, TT and TRT lobes of light reflected off the
+// cuticle, transmitted through the fiber and reflected once inside it. expects the hit tangent
+// to run along the fiber and v to go across it, as curves give them
+#[derive(Clone)]
+pub struct Hair {
+    sigma_a: Vector3<f32>,
+    eta: f32,
+    longitudinal_roughness: f32,
+    azimuthal_scale: f32,
+    alpha: f32
+}
+
+impl Hair {
+    // color is roughly the color of the fiber seen in diffuse light, the roughnesses are in [0, 1]
+    pub fn new(color: Vector3<f32>, longitudinal_roughness: f32, azimuthal_roughness: f32) -> Self {
+        let beta = azimuthal_roughness;
+        // absorption that gives color after multiple scattering, fitted by Chiang et al.
+        let fit = 5.969 - 0.215 * beta + 2.532 * beta.powi(2) - 10.73 * beta.powi(3) + 5.574 * beta.powi(4) + 0.245 * beta.powi(5);
+        let sigma_a = color.map(|c| (c.max(1e-4).ln() / fit).powi(2));
+        let azimuthal_scale = 0.626657 * (0.265 * beta + 1.194 * beta.powi(2) + 5.372 * beta.powi(22));
+        Hair { sigma_a, eta: 1.55, longitudinal_roughness, azimuthal_scale, alpha: 2f32.to_radians() }
+    }
+}
+
+impl Material for Hair {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+        let pi = std::f32::consts::PI;
+        let wo = -ray.direction().normalize();
+        let tangent = hit.tangent;
+        let sin_theta_o = wo.dot(&tangent).clamp(-1.0, 1.0);
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).sqrt();
+        // azimuths are measured in the plane across the fiber, from the offset direction
+        let z = wo - sin_theta_o * tangent;
+        let z = if z.norm_squared() > 0.0 { z.normalize() } else { hit.normal };
+        let y = tangent.cross(&z);
+        let h = 2.0 * hit.v - 1.0;
+        let gamma_o = h.asin();
+        // refraction into the fiber, with the modified index of refraction for the azimuth
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).sqrt();
+        let eta_p = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-4);
+        let gamma_t = (h / eta_p).clamp(-1.0, 1.0).asin();
+        let f = schlick(cos_theta_o * gamma_o.cos(), self.eta);
+        let transmittance = (-self.sigma_a * (2.0 * gamma_t.cos() / cos_theta_t)).map(f32::exp);
+        let lobes = [
+            Vector3::new(f, f, f),
+            (1.0 - f).powi(2) * transmittance,
+            (1.0 - f).powi(2) * f * transmittance.component_mul(&transmittance)
+        ];
+        let weights = lobes.map(|a| a.mean());
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return None
+        }
+        let mut pick = rand::thread_rng().gen::<f32>() * total;
+        let p = weights.iter().position(|&w| { pick -= w; pick < 0.0 }).unwrap_or(2);
+        // the specular cone is tilted by the cuticle scales and blurred by the roughness
+        let (shift, width) = [(2.0 * self.alpha, 1.0), (-self.alpha, 0.5), (-4.0 * self.alpha, 2.0)][p];
+        let theta_i = (-sin_theta_o.asin() + shift + width * self.longitudinal_roughness * random_normal())
+            .clamp(-0.5 * pi, 0.5 * pi);
+        let p_f = p as f32;
+        let phi_i = 0.5 * pi + 2.0 * p_f * gamma_t - 2.0 * gamma_o + p_f * pi + random_logistic(self.azimuthal_scale);
+        let direction = theta_i.sin() * tangent + theta_i.cos() * (phi_i.cos() * y + phi_i.sin() * z);
+        let scattered = Ray::new(hit.p, direction, ray.time());
+        Some((scattered, lobes[p] * total / weights[p]))
     }
 
-    fn emitted(&self, _u: f32, _v: f32, _p: &Vector3<f32>) -> Vector3<f32> { Vector3::zeros() }
+    fn emitted(&self, _hit: &HitRecord) -> Vector3<f32> { Vector3::zeros() }
 }
@@ -3,43 +3,222 @@ use nalgebra::Vector3;
 use rand::Rng;
 use crate::ray::Ray;
 use crate::hitable::{Hitable, HitRecord};
-use crate::material::Isotropic;
-use crate::texture::Texture;
+use crate::material::{Material, Isotropic};
+use crate::texture::{Texture, ScalarField};
+use crate::perlin::Perlin;
 use crate::aabb::AABB;
 
-pub struct ConstantMedium<H: Hitable, T: Texture> {
+// the parts of the ray inside the boundary, clipped to [t_min, t_max]. the intervals of the
+// boundary also run behind the ray origin, so a ray starting inside gets the part ahead of it
+fn boundary_spans<H: Hitable>(boundary: &H, ray: &Ray, t_min: f32, t_max: f32) -> Vec<(f32, f32)> {
+    boundary.intervals(ray).into_iter()
+        .map(|(enter, exit)| (enter.t.max(t_min), exit.t.min(t_max)))
+        .filter(|(t0, t1)| t0 < t1)
+        .collect()
+}
+
+// a collision inside a medium has no surface, the normal faces back along the ray so that
+// anything shading it sees the ray arriving head on
+fn scatter_record<'a>(ray: &Ray, t: f32, phase_function: &'a dyn Material) -> HitRecord<'a> {
+    let normal = -ray.direction().normalize();
+    let a = if normal.x.abs() > 0.9 { Vector3::y() } else { Vector3::x() };
+    HitRecord {
+        t,
+        u: 0.0,
+        v: 0.0,
+        p: ray.point_at_parameter(t),
+        normal,
+        geometric_normal: normal,
+        tangent: normal.cross(&a).normalize(),
+        bitangent_sign: 1.0,
+        material: phase_function
+    }
+}
+
+pub struct ConstantMedium<H: Hitable, P: Material> {
     boundary: H,
     density: f32,
-    phase_function: Isotropic<T>
+    phase_function: P
 }
 
-impl<H: Hitable, T: Texture> ConstantMedium<H, T> {
+impl<H: Hitable, T: Texture> ConstantMedium<H, Isotropic<T>> {
     pub fn new(boundary: H, density: f32, texture: T) -> Self {
-        ConstantMedium { boundary, density, phase_function: Isotropic::new(texture) }
+        ConstantMedium::with_phase_function(boundary, density, Isotropic::new(texture))
     }
 }
 
-impl<H: Hitable, T: Texture> Hitable for ConstantMedium<H, T> {
+impl<H: Hitable, P: Material> ConstantMedium<H, P> {
+    // the phase function is the material scattering the ray at every collision in the medium
+    pub fn with_phase_function(boundary: H, density: f32, phase_function: P) -> Self {
+        ConstantMedium { boundary, density, phase_function }
+    }
+}
+
+impl<H: Hitable, P: Material> Hitable for ConstantMedium<H, P> {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         let mut rng = rand::thread_rng();
-        if let Some(mut hit1) = self.boundary.hit(&ray, -f32::MAX, f32::MAX) {
-            if let Some(mut hit2) = self.boundary.hit(&ray, hit1.t + 0.0001, f32::MAX) {
-                if hit1.t < t_min { hit1.t = t_min }
-                if hit2.t > t_max { hit2.t = t_max }
-                if hit1.t < hit2.t {
-                    let distance_inside_boundary = (hit2.t - hit1.t) * ray.direction().norm();
-                    let hit_distance = -(1.0 / self.density) * rng.gen::<f32>().ln();
-                    if hit_distance < distance_inside_boundary {
-                        let t = hit1.t + hit_distance / ray.direction().norm();
-                        return Some(HitRecord {
-                            t,
-                            u: 0.0,
-                            v: 0.0,
-                            p: ray.point_at_parameter(t),
-                            normal: Vector3::new(1.0, 0.0, 0.0), // arbitrary
-                            material: &self.phase_function
-                        })
-                    }
+        let mut hit_distance = -(1.0 / self.density) * rng.gen::<f32>().ln();
+        // the free flight distance is spent across the spans inside the boundary in turn
+        for (t0, t1) in boundary_spans(&self.boundary, ray, t_min, t_max) {
+            let distance_inside_boundary = (t1 - t0) * ray.direction().norm();
+            if hit_distance < distance_inside_boundary {
+                let t = t0 + hit_distance / ray.direction().norm();
+                return Some(scatter_record(ray, t, &self.phase_function))
+            }
+            hit_distance -= distance_inside_boundary;
+        }
+        None
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.boundary.bounding_box(t0, t1)
+    }
+}
+
+// a spatially varying density of a participating medium, the field is the density at each point
+pub trait Density: ScalarField {
+    // a bound of the density everywhere, the tighter the faster the tracking
+    fn max_density(&self) -> f32;
+}
+
+// densities sampled on a regular grid spanning size from origin, interpolated trilinearly
+// and zero outside the grid
+pub struct DensityGrid {
+    values: Vec<f32>,
+    dimensions: [usize; 3],
+    origin: Vector3<f32>,
+    size: Vector3<f32>,
+    max_density: f32
+}
+
+impl DensityGrid {
+    // values are x major, then y, then z
+    pub fn new(values: Vec<f32>, dimensions: [usize; 3], origin: Vector3<f32>, size: Vector3<f32>) -> Self {
+        assert!(dimensions.iter().all(|&n| n > 1), "density grid needs at least 2 samples along every axis");
+        assert_eq!(values.len(), dimensions[0] * dimensions[1] * dimensions[2], "density samples do not match grid size");
+        let max_density = values.iter().cloned().fold(0.0, f32::max);
+        DensityGrid { values, dimensions, origin, size, max_density }
+    }
+
+    fn value(&self, i: usize, j: usize, k: usize) -> f32 {
+        self.values[i + self.dimensions[0] * (j + self.dimensions[1] * k)]
+    }
+}
+
+impl ScalarField for DensityGrid {
+    fn at(&self, p: &Vector3<f32>) -> f32 {
+        let mut cell = [0; 3];
+        let mut weight = [0.0; 3];
+        for a in 0..3 {
+            let x = (p[a] - self.origin[a]) / self.size[a] * (self.dimensions[a] - 1) as f32;
+            if x < 0.0 || x > (self.dimensions[a] - 1) as f32 {
+                return 0.0
+            }
+            cell[a] = (x as usize).min(self.dimensions[a] - 2);
+            weight[a] = x - cell[a] as f32;
+        }
+        let mut density = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let w = (if di == 0 { 1.0 - weight[0] } else { weight[0] }) *
+                        (if dj == 0 { 1.0 - weight[1] } else { weight[1] }) *
+                        (if dk == 0 { 1.0 - weight[2] } else { weight[2] });
+                    density += w * self.value(cell[0] + di, cell[1] + dj, cell[2] + dk);
+                }
+            }
+        }
+        density
+    }
+}
+
+impl Density for DensityGrid {
+    fn max_density(&self) -> f32 { self.max_density }
+}
+
+// turbulence at frequency times the position, scaled by density
+pub struct NoiseDensity {
+    noise: Perlin,
+    frequency: f32,
+    density: f32
+}
+
+impl NoiseDensity {
+    pub fn new(noise: Perlin, frequency: f32, density: f32) -> Self { NoiseDensity { noise, frequency, density } }
+}
+
+impl ScalarField for NoiseDensity {
+    fn at(&self, p: &Vector3<f32>) -> f32 {
+        self.density * self.noise.turb(&(self.frequency * p), 7)
+    }
+}
+
+impl Density for NoiseDensity {
+    // the octaves of turbulence add up to less than 2
+    fn max_density(&self) -> f32 { 2.0 * self.density }
+}
+
+// the scalar value of a texture at each point, scaled by density. textures are not bounded,
+// so the bound of the values is given
+pub struct TextureDensity<T: Texture> {
+    texture: T,
+    density: f32,
+    max_value: f32
+}
+
+impl<T: Texture> TextureDensity<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, density: f32, max_value: f32) -> Self { TextureDensity { texture, density, max_value } }
+}
+
+impl<T: Texture> ScalarField for TextureDensity<T> {
+    fn at(&self, p: &Vector3<f32>) -> f32 {
+        self.density * self.texture.scalar(0.0, 0.0, p).max(0.0)
+    }
+}
+
+impl<T: Texture> Density for TextureDensity<T> {
+    fn max_density(&self) -> f32 { self.density * self.max_value }
+}
+
+// a medium whose density varies through the boundary, sampled by delta tracking against
+// the bound of the density
+pub struct HeterogeneousMedium<H: Hitable, D: Density, P: Material> {
+    boundary: H,
+    density: D,
+    phase_function: P
+}
+
+impl<H: Hitable, D: Density, T: Texture> HeterogeneousMedium<H, D, Isotropic<T>> {
+    #[allow(dead_code)]
+    pub fn new(boundary: H, density: D, texture: T) -> Self {
+        HeterogeneousMedium::with_phase_function(boundary, density, Isotropic::new(texture))
+    }
+}
+
+impl<H: Hitable, D: Density, P: Material> HeterogeneousMedium<H, D, P> {
+    pub fn with_phase_function(boundary: H, density: D, phase_function: P) -> Self {
+        HeterogeneousMedium { boundary, density, phase_function }
+    }
+}
+
+impl<H: Hitable, D: Density, P: Material> Hitable for HeterogeneousMedium<H, D, P> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mut rng = rand::thread_rng();
+        let max_density = self.density.max_density();
+        if max_density <= 0.0 {
+            return None
+        }
+        // step through a homogeneous medium of the bound density, and accept a collision
+        // as real with the ratio of the density there to the bound
+        for (mut t, t1) in boundary_spans(&self.boundary, ray, t_min, t_max) {
+            loop {
+                t -= (1.0 - rng.gen::<f32>()).ln() / (max_density * ray.direction().norm());
+                if t >= t1 {
+                    break
+                }
+                if rng.gen::<f32>() * max_density < self.density.at(&ray.point_at_parameter(t)) {
+                    return Some(scatter_record(ray, t, &self.phase_function))
                 }
             }
         }
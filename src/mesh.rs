@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::material::Material;
+use crate::bvh::BVH;
+use crate::aabb::AABB;
+
+// Möller-Trumbore, returns the ray parameter and the barycentric coordinates of v1 and v2
+pub fn hit_triangle(ray: &Ray, v0: &Vector3<f32>, v1: &Vector3<f32>, v2: &Vector3<f32>, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = ray.direction().cross(&edge2);
+    let det = edge1.dot(&pvec);
+    if det.abs() < 1e-12 {
+        return None
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - v0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None
+    }
+    let qvec = tvec.cross(&edge1);
+    let b2 = ray.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None
+    }
+    let t = edge2.dot(&qvec) * inv_det;
+    if t < t_max && t > t_min { Some((t, b1, b2)) } else { None }
+}
+
+// area weighted average of the normals of the triangles around every vertex
+pub fn vertex_normals(positions: &[Vector3<f32>], triangles: &[[usize; 3]]) -> Vec<Vector3<f32>> {
+    let mut normals = vec![Vector3::zeros(); positions.len()];
+    for &[a, b, c] in triangles {
+        let normal = (positions[b] - positions[a]).cross(&(positions[c] - positions[a]));
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    normals.iter().map(|n| if n.norm_squared() > 0.0 { n.normalize() } else { *n }).collect()
+}
+
+struct MeshData<M: Material> {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    material: M
+}
+
+struct Triangle<M: Material> {
+    mesh: Arc<MeshData<M>>,
+    vertices: [usize; 3]
+}
+
+impl<M: Material> Hitable for Triangle<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let [a, b, c] = self.vertices;
+        let positions = &self.mesh.positions;
+        hit_triangle(ray, &positions[a], &positions[b], &positions[c], t_min, t_max).map(|(t, u, v)| {
+            let normals = &self.mesh.normals;
+            let normal = ((1.0 - u - v) * normals[a] + u * normals[b] + v * normals[c]).normalize();
+            // the flat triangle, turned to the side of the interpolated normal
+            let face = (positions[b] - positions[a]).cross(&(positions[c] - positions[a])).normalize();
+            // u and v run towards b and c, so they are mirrored when the normal is against the winding
+            let bitangent_sign = if face.dot(&normal) < 0.0 { -1.0 } else { 1.0 };
+            let geometric_normal = bitangent_sign * face;
+            let tangent = (positions[b] - positions[a]).normalize();
+            HitRecord { t, u, v, p: ray.point_at_parameter(t), normal, geometric_normal, tangent, bitangent_sign, material: &self.mesh.material }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let [a, b, c] = self.vertices.map(|i| self.mesh.positions[i]);
+        // pad the thickness of triangles lying in an axis plane
+        let padding = Vector3::new(0.0001, 0.0001, 0.0001);
+        Some(AABB::new(a.inf(&b).inf(&c) - padding, a.sup(&b).sup(&c) + padding))
+    }
+}
+
+// a triangle mesh with normals interpolated from its vertices, u and v are the barycentric
+// coordinates of the second and third vertex of the hit triangle
+pub struct Mesh {
+    bvh: BVH
+}
+
+impl Mesh {
+    pub fn new<M: Material + 'static>(positions: Vec<Vector3<f32>>, normals: Vec<Vector3<f32>>, triangles: Vec<[usize; 3]>, material: M) -> Self {
+        assert_eq!(positions.len(), normals.len(), "mesh needs a normal for every vertex");
+        let mesh = Arc::new(MeshData { positions, normals, material });
+        let triangles: Vec<Box<dyn Hitable>> = triangles.into_iter()
+            .map(|vertices| Box::new(Triangle { mesh: mesh.clone(), vertices }) as Box<dyn Hitable>)
+            .collect();
+        Mesh { bvh: BVH::new(triangles, 0.0, 1.0) }
+    }
+
+    // a mesh with smooth normals averaged from the triangles
+    #[allow(dead_code)]
+    pub fn smooth<M: Material + 'static>(positions: Vec<Vector3<f32>>, triangles: Vec<[usize; 3]>, material: M) -> Self {
+        let normals = vertex_normals(&positions, &triangles);
+        Mesh::new(positions, normals, triangles, material)
+    }
+}
+
+impl Hitable for Mesh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
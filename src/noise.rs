@@ -0,0 +1,259 @@
+use nalgebra::Vector3;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::perlin::Perlin;
+use crate::texture::{Texture, ScalarField};
+
+// a scalar field over space, of values within range
+pub trait Noise: Send + Sync {
+    fn noise(&self, p: &Vector3<f32>) -> f32;
+
+    fn range(&self) -> (f32, f32) { (-1.0, 1.0) }
+}
+
+// so that noise can drive anything taking a field, like a colour ramp
+impl<N: Noise> ScalarField for N {
+    fn at(&self, p: &Vector3<f32>) -> f32 { self.noise(p) }
+}
+
+impl Noise for Perlin {
+    fn noise(&self, p: &Vector3<f32>) -> f32 { Perlin::noise(self, p) }
+}
+
+// the numbers below 256 shuffled, twice over so that nested lookups need no wrapping
+fn permutation(rng: &mut ChaCha8Rng) -> Vec<usize> {
+    let mut p: Vec<usize> = (0..256).collect();
+    for i in (1..256).rev() {
+        // drawn as u32, since ranges of usize are sampled differently on 32 and 64 bit targets
+        p.swap(i, rng.gen_range(0..=i as u32) as usize);
+    }
+    p.extend_from_within(..);
+    p
+}
+
+// gradients towards the edge midpoints of a cube
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0]
+];
+
+// simplex noise after Perlin, as explained by Gustavson. it sums gradients over the 4 corners of
+// a tetrahedron instead of the 8 of a cube, and has no visible grid
+#[derive(Clone)]
+pub struct Simplex {
+    perm: Vec<usize>
+}
+
+impl Simplex {
+    pub fn new(seed: u64) -> Self { Simplex { perm: permutation(&mut ChaCha8Rng::seed_from_u64(seed)) } }
+}
+
+impl Noise for Simplex {
+    fn noise(&self, p: &Vector3<f32>) -> f32 {
+        const SKEW: f32 = 1.0 / 3.0;
+        const UNSKEW: f32 = 1.0 / 6.0;
+        // the cell of the skewed grid, where the tetrahedra become the halves of cubes
+        let s = (p.x + p.y + p.z) * SKEW;
+        let cell = (p + Vector3::new(s, s, s)).map(f32::floor);
+        let t = (cell.x + cell.y + cell.z) * UNSKEW;
+        let d0 = p - (cell - Vector3::new(t, t, t));
+        // the tetrahedron is picked by the order of the coordinates within the cell
+        let (first, second) = if d0.x >= d0.y {
+            if d0.y >= d0.z { ([1, 0, 0], [1, 1, 0]) }
+            else if d0.x >= d0.z { ([1, 0, 0], [1, 0, 1]) }
+            else { ([0, 0, 1], [1, 0, 1]) }
+        } else if d0.y < d0.z { ([0, 0, 1], [0, 1, 1]) }
+        else if d0.x < d0.z { ([0, 1, 0], [0, 1, 1]) }
+        else { ([0, 1, 0], [1, 1, 0]) };
+        let (i, j, k) = (cell.x as i32 as usize & 255, cell.y as i32 as usize & 255, cell.z as i32 as usize & 255);
+        [[0, 0, 0], first, second, [1, 1, 1]].iter().enumerate()
+            .map(|(n, o)| {
+                let offset = Vector3::new(o[0] as f32, o[1] as f32, o[2] as f32) - n as f32 * Vector3::new(UNSKEW, UNSKEW, UNSKEW);
+                let d = d0 - offset;
+                let falloff = 0.6 - d.norm_squared();
+                if falloff < 0.0 {
+                    0.0
+                } else {
+                    let g = GRADIENTS[self.perm[i + o[0] + self.perm[j + o[1] + self.perm[k + o[2]]]] % 12];
+                    falloff.powi(4) * (g[0] * d.x + g[1] * d.y + g[2] * d.z)
+                }
+            })
+            .sum::<f32>() * 32.0
+    }
+}
+
+// which distances to the feature points make up cellular noise
+#[derive(Clone, Copy)]
+pub enum Feature {
+    // to the closest point, dark spots in bright cells
+    F1,
+    // to the second closest point
+    #[allow(dead_code)]
+    F2,
+    // F2 - F1, dark lines along the cell edges
+    Edges
+}
+
+// cellular noise after Worley, from the distances to points scattered one in every unit cell
+#[derive(Clone)]
+pub struct Worley {
+    perm: Vec<usize>,
+    points: Vec<Vector3<f32>>,
+    feature: Feature
+}
+
+impl Worley {
+    pub fn new(seed: u64, feature: Feature) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let perm = permutation(&mut rng);
+        let points = (0..256).map(|_| Vector3::new(rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>())).collect();
+        Worley { perm, points, feature }
+    }
+
+    // the point of a cell, relative to its corner
+    fn point(&self, i: i32, j: i32, k: i32) -> Vector3<f32> {
+        self.points[self.perm[(i as usize & 255) + self.perm[(j as usize & 255) + self.perm[k as usize & 255]]]]
+    }
+}
+
+impl Noise for Worley {
+    fn noise(&self, p: &Vector3<f32>) -> f32 {
+        let cell = p.map(f32::floor);
+        let (i, j, k) = (cell.x as i32, cell.y as i32, cell.z as i32);
+        // distances are taken within the cell, as the tables repeat every 256 cells
+        let local = p - cell;
+        let mut nearest = (f32::MAX, f32::MAX);
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let point = Vector3::new(di as f32, dj as f32, dk as f32) + self.point(i + di, j + dj, k + dk);
+                    let d = (point - local).norm();
+                    if d < nearest.0 {
+                        nearest = (d, nearest.0);
+                    } else if d < nearest.1 {
+                        nearest.1 = d;
+                    }
+                }
+            }
+        }
+        match self.feature {
+            Feature::F1 => nearest.0,
+            Feature::F2 => nearest.1,
+            Feature::Edges => nearest.1 - nearest.0
+        }
+    }
+
+    fn range(&self) -> (f32, f32) { (0.0, 1.0) }
+}
+
+// fractional Brownian motion, octaves of noise each lacunarity times the frequency and gain
+// times the amplitude of the one before
+#[derive(Clone)]
+pub struct Fbm<N: Noise> {
+    noise: N,
+    octaves: usize,
+    lacunarity: f32,
+    gain: f32
+}
+
+impl<N: Noise> Fbm<N> {
+    pub fn new(noise: N, octaves: usize, lacunarity: f32, gain: f32) -> Self { Fbm { noise, octaves, lacunarity, gain } }
+
+    fn amplitude_sum(&self) -> f32 { (0..self.octaves).map(|o| self.gain.powi(o as i32)).sum() }
+}
+
+impl<N: Noise> Noise for Fbm<N> {
+    fn noise(&self, p: &Vector3<f32>) -> f32 {
+        let mut sum = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for _ in 0..self.octaves {
+            sum += amplitude * self.noise.noise(&(frequency * p));
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        sum
+    }
+
+    fn range(&self) -> (f32, f32) {
+        let (low, high) = self.noise.range();
+        let amplitudes = self.amplitude_sum();
+        (low * amplitudes, high * amplitudes)
+    }
+}
+
+// ridged multifractal after Musgrave, octaves of inverted absolute noise that make sharp crests.
+// every octave is weighted by the one before, so the detail gathers on the ridges
+#[derive(Clone)]
+pub struct Ridged<N: Noise> {
+    fbm: Fbm<N>
+}
+
+impl<N: Noise> Ridged<N> {
+    pub fn new(noise: N, octaves: usize, lacunarity: f32, gain: f32) -> Self { Ridged { fbm: Fbm::new(noise, octaves, lacunarity, gain) } }
+}
+
+impl<N: Noise> Noise for Ridged<N> {
+    fn noise(&self, p: &Vector3<f32>) -> f32 {
+        let mut sum = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut weight = 1.0;
+        for _ in 0..self.fbm.octaves {
+            let signal = (1.0 - self.fbm.noise.noise(&(frequency * p)).abs()).max(0.0).powi(2) * weight;
+            sum += amplitude * signal;
+            weight = signal.clamp(0.0, 1.0);
+            frequency *= self.fbm.lacunarity;
+            amplitude *= self.fbm.gain;
+        }
+        sum
+    }
+
+    fn range(&self) -> (f32, f32) { (0.0, self.fbm.amplitude_sum()) }
+}
+
+// noise looked up at positions displaced by three lookups of a warp noise, scaled by strength
+#[derive(Clone)]
+pub struct DomainWarp<N: Noise, W: Noise> {
+    noise: N,
+    warp: W,
+    strength: f32
+}
+
+impl<N: Noise, W: Noise> DomainWarp<N, W> {
+    pub fn new(noise: N, warp: W, strength: f32) -> Self { DomainWarp { noise, warp, strength } }
+}
+
+impl<N: Noise, W: Noise> Noise for DomainWarp<N, W> {
+    fn noise(&self, p: &Vector3<f32>) -> f32 {
+        // far apart lookups of the same noise are unrelated enough for the three axes
+        let displacement = Vector3::new(
+            self.warp.noise(p),
+            self.warp.noise(&(p + Vector3::new(5.2, 1.3, 2.8))),
+            self.warp.noise(&(p + Vector3::new(1.7, 9.2, 4.1))));
+        self.noise.noise(&(p + self.strength * displacement))
+    }
+
+    fn range(&self) -> (f32, f32) { self.noise.range() }
+}
+
+// noise at frequency times the position, as gray from black at the low end of its range to white
+// at the high end
+#[derive(Clone)]
+pub struct NoiseFieldTexture<N: Noise> {
+    noise: N,
+    frequency: f32
+}
+
+impl<N: Noise> NoiseFieldTexture<N> {
+    pub fn new(noise: N, frequency: f32) -> Self { NoiseFieldTexture { noise, frequency } }
+}
+
+impl<N: Noise> Texture for NoiseFieldTexture<N> {
+    fn value(&self, _u: f32, _v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        let (low, high) = self.noise.range();
+        let gray = ((self.noise.noise(&(self.frequency * p)) - low) / (high - low)).clamp(0.0, 1.0);
+        Vector3::new(gray, gray, gray)
+    }
+}
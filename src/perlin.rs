@@ -1,8 +1,8 @@
 use nalgebra::Vector3;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-fn perlin_generate() -> Vec<Vector3<f32>> {
-    let mut rng = rand::thread_rng();
+fn perlin_generate<R: Rng>(rng: &mut R) -> Vec<Vector3<f32>> {
     let mut p = Vec::with_capacity(256);
     for _ in 0..256 {
         p.push(Vector3::new(
@@ -13,20 +13,20 @@ fn perlin_generate() -> Vec<Vector3<f32>> {
     p
 }
 
-fn permute(p: &mut [usize], n: usize) {
-    let mut rng = rand::thread_rng();
+fn permute<R: Rng>(rng: &mut R, p: &mut [usize], n: usize) {
     for i in (0..n as usize).rev() {
-        let target = rng.gen_range(0..=i);
+        // drawn as u32, since ranges of usize are sampled differently on 32 and 64 bit targets
+        let target = rng.gen_range(0..=i as u32) as usize;
         p.swap(i, target);
     }
 }
 
-fn perlin_generate_perm() -> Vec<usize> {
+fn perlin_generate_perm<R: Rng>(rng: &mut R) -> Vec<usize> {
     let mut p = Vec::with_capacity(256);
     for i in 0..256 {
         p.push(i);
     }
-    permute(&mut p, 256);
+    permute(rng, &mut p, 256);
     p
 }
 
@@ -58,28 +58,36 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Self {
+    // a different pattern on every run
+    #[allow(dead_code)]
+    pub fn new() -> Self { Perlin::with_seed(rand::random()) }
+
+    // the same pattern for the same seed, on every run and machine. ChaCha keeps its output
+    // across rand versions, unlike StdRng
+    pub fn with_seed(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
         Perlin {
-            ran_vec: perlin_generate(),
-            perm_x: perlin_generate_perm(),
-            perm_y: perlin_generate_perm(),
-            perm_z: perlin_generate_perm()
+            ran_vec: perlin_generate(&mut rng),
+            perm_x: perlin_generate_perm(&mut rng),
+            perm_y: perlin_generate_perm(&mut rng),
+            perm_z: perlin_generate_perm(&mut rng)
         }
     }
 
-    fn noise(&self, p: &Vector3<f32>) -> f32 {
+    pub fn noise(&self, p: &Vector3<f32>) -> f32 {
         let u = p.x - f32::floor(p.x);
         let v = p.y - f32::floor(p.y);
         let w = p.z - f32::floor(p.z);
-        let i = f32::floor(p.x) as usize;
-        let j = f32::floor(p.y) as usize;
-        let k = f32::floor(p.z) as usize;
+        // through i32, so that negative cells wrap around the tables instead of all becoming 0
+        let i = f32::floor(p.x) as i32 as usize;
+        let j = f32::floor(p.y) as i32 as usize;
+        let k = f32::floor(p.z) as i32 as usize;
         let mut c = [[[Vector3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
         for di in 0..2 {
             for dj in 0..2 {
                 for dk in 0..2 {
                     c[di][dj][dk] =
-                        self.ran_vec[self.perm_x[(i + di) & 255] ^ self.perm_y[(j + dj) & 255] ^ self.perm_z[(k + dk) & 255]]
+                        self.ran_vec[self.perm_x[i.wrapping_add(di) & 255] ^ self.perm_y[j.wrapping_add(dj) & 255] ^ self.perm_z[k.wrapping_add(dk) & 255]]
                 }
             }
         };
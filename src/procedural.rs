@@ -0,0 +1,89 @@
+use nalgebra::Vector3;
+use crate::noise::Noise;
+use crate::texture::Texture;
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// growth rings around the y axis, scale rings per unit distance. the noise bends the rings and
+// lightens the grain along them
+pub struct Wood<N: Noise> {
+    noise: N,
+    scale: f32,
+    light: Vector3<f32>,
+    dark: Vector3<f32>
+}
+
+impl<N: Noise> Wood<N> {
+    pub fn new(noise: N, scale: f32, light: Vector3<f32>, dark: Vector3<f32>) -> Self { Wood { noise, scale, light, dark } }
+}
+
+impl<N: Noise> Texture for Wood<N> {
+    fn value(&self, _u: f32, _v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        let q = self.scale * p;
+        // the trunk wanders slowly along its length
+        let radius = (q.x * q.x + q.z * q.z).sqrt() + 0.4 * self.noise.noise(&(0.25 * q));
+        let ring = radius - radius.floor();
+        // early wood grows wide and light, late wood narrow and dark
+        let late = smoothstep(0.6, 0.8, ring) * (1.0 - smoothstep(0.9, 1.0, ring));
+        let grain = 0.1 * self.noise.noise(&Vector3::new(8.0 * q.x, 0.5 * q.y, 8.0 * q.z));
+        self.light.lerp(&self.dark, (late + grain).clamp(0.0, 1.0))
+    }
+}
+
+// veins of vein colour in base colour, in bands along x of scale radians per unit distance,
+// bent by the absolute value of the noise
+pub struct Marble<N: Noise> {
+    noise: N,
+    scale: f32,
+    base: Vector3<f32>,
+    vein: Vector3<f32>
+}
+
+impl<N: Noise> Marble<N> {
+    pub fn new(noise: N, scale: f32, base: Vector3<f32>, vein: Vector3<f32>) -> Self { Marble { noise, scale, base, vein } }
+}
+
+impl<N: Noise> Texture for Marble<N> {
+    fn value(&self, _u: f32, _v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        let turbulence = self.noise.noise(p).abs();
+        // the veins are where the sine crosses zero, narrowed by a high power
+        let s = (self.scale * p.x + 5.0 * turbulence).sin();
+        self.base.lerp(&self.vein, (1.0 - s.abs()).powi(4))
+    }
+}
+
+// bricks twice as wide as high in a running bond, scale rows per unit of v. every brick is a
+// little lighter or darker than the brick colour
+#[derive(Clone)]
+pub struct Brick {
+    scale: f32,
+    brick: Vector3<f32>,
+    mortar: Vector3<f32>
+}
+
+impl Brick {
+    pub fn new(scale: f32, brick: Vector3<f32>, mortar: Vector3<f32>) -> Self { Brick { scale, brick, mortar } }
+}
+
+impl Texture for Brick {
+    fn value(&self, u: f32, v: f32, _p: &Vector3<f32>) -> Vector3<f32> {
+        // mortar joints as a fraction of the brick height
+        const JOINT: f32 = 0.08;
+        let y = v * self.scale;
+        let row = y.floor();
+        // every other row is shifted by half a brick
+        let x = 0.5 * u * self.scale + if row.rem_euclid(2.0) == 1.0 { 0.5 } else { 0.0 };
+        let column = x.floor();
+        let (fx, fy) = (2.0 * (x - column), y - row);
+        if !(0.5 * JOINT..=2.0 - 0.5 * JOINT).contains(&fx) || !(0.5 * JOINT..=1.0 - 0.5 * JOINT).contains(&fy) {
+            return self.mortar
+        }
+        // a hash of the brick for its shade
+        let hash = (column as i32 as u32).wrapping_mul(73856093) ^ (row as i32 as u32).wrapping_mul(19349663);
+        let hash = hash.wrapping_mul(0x9e3779b9) >> 16;
+        (0.8 + 0.4 * (hash & 0xff) as f32 / 255.0) * self.brick
+    }
+}
@@ -0,0 +1,67 @@
+use nalgebra::Vector3;
+use rand::Rng;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord, Sampleable};
+use crate::material::Material;
+use crate::aabb::AABB;
+
+// a parallelogram with a corner in q and edges u and v, facing towards u x v
+pub struct Quad<M: Material> {
+    q: Vector3<f32>,
+    u: Vector3<f32>,
+    v: Vector3<f32>,
+    normal: Vector3<f32>,
+    d: f32,
+    w: Vector3<f32>,
+    material: M
+}
+
+impl<M: Material> Quad<M> {
+    pub fn new(q: Vector3<f32>, u: Vector3<f32>, v: Vector3<f32>, material: M) -> Self {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+        let d = normal.dot(&q);
+        let w = n / n.dot(&n);
+        Quad { q, u, v, normal, d, w, material }
+    }
+}
+
+impl<M: Material> Hitable for Quad<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let denominator = self.normal.dot(&ray.direction());
+        if denominator.abs() < 1e-8 {
+            return None
+        }
+        let t = (self.d - self.normal.dot(&ray.origin())) / denominator;
+        if t < t_min || t > t_max {
+            return None
+        }
+        let p = ray.point_at_parameter(t);
+        // coordinates of the hit point along the edges
+        let planar = p - self.q;
+        let u = self.w.dot(&planar.cross(&self.v));
+        let v = self.w.dot(&self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            None
+        } else {
+            Some(HitRecord { t, u, v, p, normal: self.normal, geometric_normal: self.normal, tangent: self.u.normalize(), bitangent_sign: 1.0, material: &self.material })
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let corners = [self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let (min, max) = corners.iter().fold((self.q, self.q), |(min, max), c| (min.inf(c), max.sup(c)));
+        // pad the thickness of quads lying in an axis plane
+        let padding = Vector3::new(0.0001, 0.0001, 0.0001);
+        Some(AABB::new(min - padding, max + padding))
+    }
+}
+
+impl<M: Material> Sampleable for Quad<M> {
+    fn area(&self) -> f32 { self.u.cross(&self.v).norm() }
+
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let mut rng = rand::thread_rng();
+        (self.q + rng.gen::<f32>() * self.u + rng.gen::<f32>() * self.v, self.normal)
+    }
+}
@@ -0,0 +1,408 @@
+use std::f32;
+use nalgebra::Vector3;
+use rand::Rng;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord, Sampleable};
+use crate::material::Material;
+use crate::aabb::AABB;
+
+// Quadrics are surfaces of revolution around a vertical axis through center, starting at
+// center.y and swept from the +x towards the +z axis by phi_max degrees. u follows the sweep.
+// Seen from outside, the sweep turns clockwise from v on the sides, which goes up, and on the
+// top caps, where v goes inwards, so their uv are mirrored against the normal.
+
+fn get_phi(p: &Vector3<f32>) -> f32 {
+    let phi = p.z.atan2(p.x);
+    if phi < 0.0 { phi + 2.0 * f32::consts::PI } else { phi }
+}
+
+fn get_tangent(p: &Vector3<f32>) -> Vector3<f32> {
+    let tangent = Vector3::new(-p.z, 0.0, p.x);
+    if tangent.norm_squared() > 0.0 { tangent.normalize() } else { Vector3::z() }
+}
+
+fn point_on_circle(radius: f32, phi: f32, y: f32) -> Vector3<f32> {
+    Vector3::new(radius * phi.cos(), y, radius * phi.sin())
+}
+
+// nearest root of a * t^2 + 2 * b * t + c in (t_min, t_max) whose point lies in [0, height] and in the sweep
+fn hit_side(o: &Vector3<f32>, d: &Vector3<f32>, (a, b, c): (f32, f32, f32), height: f32, phi_max: f32, t_min: f32, t_max: f32) -> Option<(f32, Vector3<f32>)> {
+    let discriminant = b.powi(2) - a * c;
+    if a.abs() < 1e-12 || discriminant < 0.0 {
+        return None
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    let (t0, t1) = ((-b - sqrt_discriminant) / a, (-b + sqrt_discriminant) / a);
+    let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+    [t0, t1].iter()
+        .filter(|&&t| t > t_min && t < t_max)
+        .map(|&t| (t, o + t * d))
+        .find(|(_, p)| p.y >= 0.0 && p.y <= height && get_phi(p) <= phi_max)
+}
+
+// a horizontal ring at height y between inner_radius and radius, within the sweep
+fn hit_cap(o: &Vector3<f32>, d: &Vector3<f32>, y: f32, (inner_radius, radius): (f32, f32), phi_max: f32, t_min: f32, t_max: f32) -> Option<(f32, Vector3<f32>)> {
+    if d.y == 0.0 {
+        return None
+    }
+    let t = (y - o.y) / d.y;
+    if t <= t_min || t >= t_max {
+        return None
+    }
+    let p = o + t * d;
+    let distance_squared = p.x.powi(2) + p.z.powi(2);
+    if distance_squared > radius.powi(2) || distance_squared < inner_radius.powi(2) || get_phi(&p) > phi_max {
+        None
+    } else {
+        Some((t, p))
+    }
+}
+
+fn cap_uv(p: &Vector3<f32>, inner_radius: f32, radius: f32, phi_max: f32) -> (f32, f32) {
+    let distance = (p.x.powi(2) + p.z.powi(2)).sqrt();
+    (get_phi(p) / phi_max, (radius - distance) / (radius - inner_radius))
+}
+
+fn sample_cap(y: f32, inner_radius: f32, radius: f32, phi_max: f32) -> Vector3<f32> {
+    let mut rng = rand::thread_rng();
+    let r = (inner_radius.powi(2) + rng.gen::<f32>() * (radius.powi(2) - inner_radius.powi(2))).sqrt();
+    point_on_circle(r, phi_max * rng.gen::<f32>(), y)
+}
+
+fn cap_area(inner_radius: f32, radius: f32, phi_max: f32) -> f32 {
+    0.5 * phi_max * (radius.powi(2) - inner_radius.powi(2))
+}
+
+// bounding box of the full revolution, the sweep can only make the surface smaller
+fn revolution_box(center: &Vector3<f32>, radius: f32, height: f32) -> AABB {
+    AABB::new(
+        center + Vector3::new(-radius, -0.0001, -radius),
+        center + Vector3::new(radius, height + 0.0001, radius))
+}
+
+// samples a height with density proportional to density(y) by rejection
+fn sample_height(height: f32, density: impl Fn(f32) -> f32) -> f32 {
+    let mut rng = rand::thread_rng();
+    let max = (0..=16).map(|i| density(height * i as f32 / 16.0)).fold(0.0, f32::max);
+    loop {
+        let y = height * rng.gen::<f32>();
+        if rng.gen::<f32>() * max <= density(y) {
+            return y
+        }
+    }
+}
+
+pub struct Cylinder<M: Material> {
+    center: Vector3<f32>,
+    radius: f32,
+    height: f32,
+    phi_max: f32,
+    capped: bool,
+    material: M
+}
+
+impl<M: Material> Cylinder<M> {
+    pub fn new(center: Vector3<f32>, radius: f32, height: f32, phi_max: f32, capped: bool, material: M) -> Self {
+        let phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+        Cylinder { center, radius, height, phi_max, capped, material }
+    }
+}
+
+impl<M: Material> Hitable for Cylinder<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord> {
+        let o = ray.origin() - self.center;
+        let d = ray.direction();
+        let coefficients = (d.x.powi(2) + d.z.powi(2), o.x * d.x + o.z * d.z, o.x.powi(2) + o.z.powi(2) - self.radius.powi(2));
+        let mut hit_anything = None;
+        if let Some((t, p)) = hit_side(&o, &d, coefficients, self.height, self.phi_max, t_min, t_max) {
+            let normal = Vector3::new(p.x, 0.0, p.z) / self.radius;
+            let (u, v) = (get_phi(&p) / self.phi_max, p.y / self.height);
+            hit_anything = Some((t, p, normal, u, v));
+            t_max = t;
+        }
+        if self.capped {
+            for (y, normal) in [(0.0, -Vector3::y()), (self.height, Vector3::y())] {
+                if let Some((t, p)) = hit_cap(&o, &d, y, (0.0, self.radius), self.phi_max, t_min, t_max) {
+                    let (u, v) = cap_uv(&p, 0.0, self.radius, self.phi_max);
+                    hit_anything = Some((t, p, normal, u, v));
+                    t_max = t;
+                }
+            }
+        }
+        hit_anything.map(|(t, p, normal, u, v)| {
+            let bitangent_sign = if normal.y < 0.0 { 1.0 } else { -1.0 };
+            HitRecord { t, u, v, p: ray.point_at_parameter(t), normal, geometric_normal: normal, tangent: get_tangent(&p), bitangent_sign, material: &self.material }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(revolution_box(&self.center, self.radius, self.height))
+    }
+}
+
+impl<M: Material> Sampleable for Cylinder<M> {
+    fn area(&self) -> f32 {
+        let side = self.phi_max * self.radius * self.height;
+        if self.capped { side + 2.0 * cap_area(0.0, self.radius, self.phi_max) } else { side }
+    }
+
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let mut rng = rand::thread_rng();
+        let side = self.phi_max * self.radius * self.height;
+        let choice = rng.gen::<f32>() * self.area();
+        let (p, normal) = if choice < side {
+            let p = point_on_circle(self.radius, self.phi_max * rng.gen::<f32>(), self.height * rng.gen::<f32>());
+            (p, Vector3::new(p.x, 0.0, p.z) / self.radius)
+        } else if choice < side + cap_area(0.0, self.radius, self.phi_max) {
+            (sample_cap(0.0, 0.0, self.radius, self.phi_max), -Vector3::y())
+        } else {
+            (sample_cap(self.height, 0.0, self.radius, self.phi_max), Vector3::y())
+        };
+        (self.center + p, normal)
+    }
+}
+
+// a cone with its base on center and its apex height above it
+pub struct Cone<M: Material> {
+    center: Vector3<f32>,
+    radius: f32,
+    height: f32,
+    phi_max: f32,
+    capped: bool,
+    material: M
+}
+
+impl<M: Material> Cone<M> {
+    pub fn new(center: Vector3<f32>, radius: f32, height: f32, phi_max: f32, capped: bool, material: M) -> Self {
+        let phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+        Cone { center, radius, height, phi_max, capped, material }
+    }
+
+    fn side_area(&self) -> f32 {
+        0.5 * self.phi_max * self.radius * (self.radius.powi(2) + self.height.powi(2)).sqrt()
+    }
+
+    fn side_normal(&self, p: &Vector3<f32>) -> Vector3<f32> {
+        let k = (self.radius / self.height).powi(2);
+        Vector3::new(p.x, k * (self.height - p.y), p.z).normalize()
+    }
+}
+
+impl<M: Material> Hitable for Cone<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord> {
+        let o = ray.origin() - self.center;
+        let d = ray.direction();
+        let k = (self.radius / self.height).powi(2);
+        let h = self.height - o.y;
+        let coefficients = (
+            d.x.powi(2) + d.z.powi(2) - k * d.y.powi(2),
+            o.x * d.x + o.z * d.z + k * h * d.y,
+            o.x.powi(2) + o.z.powi(2) - k * h.powi(2));
+        let mut hit_anything = None;
+        if let Some((t, p)) = hit_side(&o, &d, coefficients, self.height, self.phi_max, t_min, t_max) {
+            let (u, v) = (get_phi(&p) / self.phi_max, p.y / self.height);
+            hit_anything = Some((t, p, self.side_normal(&p), u, v));
+            t_max = t;
+        }
+        if self.capped {
+            if let Some((t, p)) = hit_cap(&o, &d, 0.0, (0.0, self.radius), self.phi_max, t_min, t_max) {
+                let (u, v) = cap_uv(&p, 0.0, self.radius, self.phi_max);
+                hit_anything = Some((t, p, -Vector3::y(), u, v));
+            }
+        }
+        hit_anything.map(|(t, p, normal, u, v)| {
+            let bitangent_sign = if normal.y < 0.0 { 1.0 } else { -1.0 };
+            HitRecord { t, u, v, p: ray.point_at_parameter(t), normal, geometric_normal: normal, tangent: get_tangent(&p), bitangent_sign, material: &self.material }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(revolution_box(&self.center, self.radius, self.height))
+    }
+}
+
+impl<M: Material> Sampleable for Cone<M> {
+    fn area(&self) -> f32 {
+        if self.capped { self.side_area() + cap_area(0.0, self.radius, self.phi_max) } else { self.side_area() }
+    }
+
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let mut rng = rand::thread_rng();
+        let (p, normal) = if rng.gen::<f32>() * self.area() < self.side_area() {
+            // the circumference shrinks linearly towards the apex
+            let y = self.height * (1.0 - rng.gen::<f32>().sqrt());
+            let p = point_on_circle(self.radius * (1.0 - y / self.height), self.phi_max * rng.gen::<f32>(), y);
+            (p, self.side_normal(&p))
+        } else {
+            (sample_cap(0.0, 0.0, self.radius, self.phi_max), -Vector3::y())
+        };
+        (self.center + p, normal)
+    }
+}
+
+// a horizontal disk facing up, or an annulus when inner_radius is not zero
+pub struct Disk<M: Material> {
+    center: Vector3<f32>,
+    radius: f32,
+    inner_radius: f32,
+    phi_max: f32,
+    material: M
+}
+
+impl<M: Material> Disk<M> {
+    pub fn new(center: Vector3<f32>, radius: f32, inner_radius: f32, phi_max: f32, material: M) -> Self {
+        let phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+        Disk { center, radius, inner_radius, phi_max, material }
+    }
+}
+
+impl<M: Material> Hitable for Disk<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = ray.origin() - self.center;
+        hit_cap(&o, &ray.direction(), 0.0, (self.inner_radius, self.radius), self.phi_max, t_min, t_max).map(|(t, p)| {
+            let (u, v) = cap_uv(&p, self.inner_radius, self.radius, self.phi_max);
+            HitRecord { t, u, v, p: ray.point_at_parameter(t), normal: Vector3::y(), geometric_normal: Vector3::y(), tangent: get_tangent(&p), bitangent_sign: -1.0, material: &self.material }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(revolution_box(&self.center, self.radius, 0.0))
+    }
+}
+
+impl<M: Material> Sampleable for Disk<M> {
+    fn area(&self) -> f32 { cap_area(self.inner_radius, self.radius, self.phi_max) }
+
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        (self.center + sample_cap(0.0, self.inner_radius, self.radius, self.phi_max), Vector3::y())
+    }
+}
+
+// a paraboloid with its apex on center, opening up to radius at height
+pub struct Paraboloid<M: Material> {
+    center: Vector3<f32>,
+    radius: f32,
+    height: f32,
+    phi_max: f32,
+    material: M
+}
+
+impl<M: Material> Paraboloid<M> {
+    pub fn new(center: Vector3<f32>, radius: f32, height: f32, phi_max: f32, material: M) -> Self {
+        let phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+        Paraboloid { center, radius, height, phi_max, material }
+    }
+
+    // x^2 + z^2 = k * y
+    fn k(&self) -> f32 { self.radius.powi(2) / self.height }
+
+    fn normal(&self, p: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(2.0 * p.x, -self.k(), 2.0 * p.z).normalize()
+    }
+}
+
+impl<M: Material> Hitable for Paraboloid<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = ray.origin() - self.center;
+        let d = ray.direction();
+        let k = self.k();
+        let coefficients = (d.x.powi(2) + d.z.powi(2), o.x * d.x + o.z * d.z - 0.5 * k * d.y, o.x.powi(2) + o.z.powi(2) - k * o.y);
+        hit_side(&o, &d, coefficients, self.height, self.phi_max, t_min, t_max).map(|(t, p)| {
+            let (u, v) = (get_phi(&p) / self.phi_max, p.y / self.height);
+            HitRecord { t, u, v, p: ray.point_at_parameter(t), normal: self.normal(&p), geometric_normal: self.normal(&p), tangent: get_tangent(&p), bitangent_sign: -1.0, material: &self.material }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(revolution_box(&self.center, self.radius, self.height))
+    }
+}
+
+impl<M: Material> Sampleable for Paraboloid<M> {
+    fn area(&self) -> f32 {
+        let k = self.k();
+        let integral = |y: f32| (k * y + k.powi(2) / 4.0).powf(1.5);
+        self.phi_max * 2.0 / (3.0 * k) * (integral(self.height) - integral(0.0))
+    }
+
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let k = self.k();
+        let y = sample_height(self.height, |y| (k * y + k.powi(2) / 4.0).sqrt());
+        let p = point_on_circle((k * y).sqrt(), self.phi_max * rand::thread_rng().gen::<f32>(), y);
+        (self.center + p, self.normal(&p))
+    }
+}
+
+// a hyperboloid of one sheet, narrowest with waist_radius halfway up and with radius at both ends
+pub struct Hyperboloid<M: Material> {
+    center: Vector3<f32>,
+    radius: f32,
+    waist_radius: f32,
+    height: f32,
+    phi_max: f32,
+    material: M
+}
+
+impl<M: Material> Hyperboloid<M> {
+    pub fn new(center: Vector3<f32>, radius: f32, waist_radius: f32, height: f32, phi_max: f32, material: M) -> Self {
+        let phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+        Hyperboloid { center, radius, waist_radius, height, phi_max, material }
+    }
+
+    // x^2 + z^2 - k * (y - height / 2)^2 = waist_radius^2
+    fn k(&self) -> f32 { (self.radius.powi(2) - self.waist_radius.powi(2)) / (0.5 * self.height).powi(2) }
+
+    fn radius_at(&self, y: f32) -> f32 {
+        (self.waist_radius.powi(2) + self.k() * (y - 0.5 * self.height).powi(2)).sqrt()
+    }
+
+    fn normal(&self, p: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(p.x, -self.k() * (p.y - 0.5 * self.height), p.z).normalize()
+    }
+
+    // circumference weight of the surface at height y, up to the constant phi_max
+    fn density(&self, y: f32) -> f32 {
+        let k = self.k();
+        (self.waist_radius.powi(2) + k * (1.0 + k) * (y - 0.5 * self.height).powi(2)).sqrt()
+    }
+}
+
+impl<M: Material> Hitable for Hyperboloid<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = ray.origin() - self.center;
+        let d = ray.direction();
+        let k = self.k();
+        let y = o.y - 0.5 * self.height;
+        let coefficients = (
+            d.x.powi(2) + d.z.powi(2) - k * d.y.powi(2),
+            o.x * d.x + o.z * d.z - k * y * d.y,
+            o.x.powi(2) + o.z.powi(2) - k * y.powi(2) - self.waist_radius.powi(2));
+        hit_side(&o, &d, coefficients, self.height, self.phi_max, t_min, t_max).map(|(t, p)| {
+            let (u, v) = (get_phi(&p) / self.phi_max, p.y / self.height);
+            HitRecord { t, u, v, p: ray.point_at_parameter(t), normal: self.normal(&p), geometric_normal: self.normal(&p), tangent: get_tangent(&p), bitangent_sign: -1.0, material: &self.material }
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(revolution_box(&self.center, self.radius.max(self.waist_radius), self.height))
+    }
+}
+
+impl<M: Material> Sampleable for Hyperboloid<M> {
+    fn area(&self) -> f32 {
+        // Simpson's rule, the integrand is smooth over the whole height
+        let n = 64;
+        let h = self.height / n as f32;
+        let sum: f32 = (0..=n).map(|i| {
+            let weight = if i == 0 || i == n { 1.0 } else if i % 2 == 1 { 4.0 } else { 2.0 };
+            weight * self.density(i as f32 * h)
+        }).sum();
+        self.phi_max * sum * h / 3.0
+    }
+
+    fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let y = sample_height(self.height, |y| self.density(y));
+        let p = point_on_circle(self.radius_at(y), self.phi_max * rand::thread_rng().gen::<f32>(), y);
+        (self.center + p, self.normal(&p))
+    }
+}
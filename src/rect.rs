@@ -47,7 +47,9 @@ impl<M: Material> Hitable for AARect<M> {
                 let p = ray.point_at_parameter(t);
                 let mut normal = Vector3::zeros();
                 normal[k_axis] = 1.0;
-                Some(HitRecord { t, u, v, p, normal, material: &self.material })
+                let mut tangent = Vector3::zeros();
+                tangent[a_axis] = 1.0;
+                Some(HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: 1.0, material: &self.material })
             }
         }
     }
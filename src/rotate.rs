@@ -76,12 +76,20 @@ impl<H: Hitable> Hitable for Rotate<H> {
         self.hitable.hit(&rotated_ray, t_min, t_max).map(|mut hit| {
             let mut p = hit.p;
             let mut normal = hit.normal;
+            let mut geometric_normal = hit.geometric_normal;
+            let mut tangent = hit.tangent;
             p[a_axis] = self.cos_theta * hit.p[a_axis] - self.sin_theta * hit.p[b_axis];
             p[b_axis] = self.sin_theta * hit.p[a_axis] + self.cos_theta * hit.p[b_axis];
             normal[a_axis] = self.cos_theta * hit.normal[a_axis] - self.sin_theta * hit.normal[b_axis];
             normal[b_axis] = self.sin_theta * hit.normal[a_axis] + self.cos_theta * hit.normal[b_axis];
+            geometric_normal[a_axis] = self.cos_theta * hit.geometric_normal[a_axis] - self.sin_theta * hit.geometric_normal[b_axis];
+            geometric_normal[b_axis] = self.sin_theta * hit.geometric_normal[a_axis] + self.cos_theta * hit.geometric_normal[b_axis];
+            tangent[a_axis] = self.cos_theta * hit.tangent[a_axis] - self.sin_theta * hit.tangent[b_axis];
+            tangent[b_axis] = self.sin_theta * hit.tangent[a_axis] + self.cos_theta * hit.tangent[b_axis];
             hit.p = p;
             hit.normal = normal;
+            hit.geometric_normal = geometric_normal;
+            hit.tangent = tangent;
             hit
         })
     }
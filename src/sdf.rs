@@ -0,0 +1,112 @@
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::material::Material;
+use crate::aabb::AABB;
+
+const MAX_STEPS: usize = 256;
+const EPSILON: f32 = 0.0001;
+
+// a surface defined by a signed distance function, negative inside, found by sphere tracing
+// within a user provided bounding box. u and v are the position across the box in x and y.
+pub struct SDF<F: Fn(&Vector3<f32>) -> f32 + Send + Sync, M: Material> {
+    distance: F,
+    bbox: AABB,
+    material: M
+}
+
+impl<F: Fn(&Vector3<f32>) -> f32 + Send + Sync, M: Material> SDF<F, M> {
+    pub fn new(distance: F, bbox: AABB, material: M) -> Self { SDF { distance, bbox, material } }
+
+    // central differences of the distance field
+    fn gradient(&self, p: &Vector3<f32>) -> Vector3<f32> {
+        let dx = Vector3::new(EPSILON, 0.0, 0.0);
+        let dy = Vector3::new(0.0, EPSILON, 0.0);
+        let dz = Vector3::new(0.0, 0.0, EPSILON);
+        Vector3::new(
+            (self.distance)(&(p + dx)) - (self.distance)(&(p - dx)),
+            (self.distance)(&(p + dy)) - (self.distance)(&(p - dy)),
+            (self.distance)(&(p + dz)) - (self.distance)(&(p - dz)))
+    }
+}
+
+impl<F: Fn(&Vector3<f32>) -> f32 + Send + Sync, M: Material> Hitable for SDF<F, M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t0, t1) = self.bbox.intersect(ray, t_min, t_max)?;
+        let length = ray.direction().norm();
+        // march on the side the ray is heading to, so that a ray leaving the surface
+        // it was scattered from does not hit it again straight away
+        let start = ray.point_at_parameter(t0);
+        let distance = (self.distance)(&start);
+        let side = if distance.abs() < EPSILON {
+            if self.gradient(&start).dot(&ray.direction()) > 0.0 { 1.0 } else { -1.0 }
+        } else {
+            distance.signum()
+        };
+        let mut t = t0;
+        let mut left_surface = false;
+        for _ in 0..MAX_STEPS {
+            let p = ray.point_at_parameter(t);
+            let distance = side * (self.distance)(&p);
+            if distance < EPSILON {
+                if left_surface {
+                    let normal = self.gradient(&p).normalize();
+                    let extent = self.bbox.max - self.bbox.min;
+                    let u = (p.x - self.bbox.min.x) / extent.x;
+                    let v = (p.y - self.bbox.min.y) / extent.y;
+                    return Some(HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent: Vector3::x(), bitangent_sign: if normal.z < 0.0 { -1.0 } else { 1.0 }, material: &self.material })
+                }
+            } else {
+                left_surface = true;
+            }
+            t += distance.max(EPSILON) / length;
+            if t > t1 {
+                break
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+pub fn sphere(center: Vector3<f32>, radius: f32) -> impl Fn(&Vector3<f32>) -> f32 {
+    move |p| (p - center).norm() - radius
+}
+
+pub fn cuboid(center: Vector3<f32>, half_size: Vector3<f32>) -> impl Fn(&Vector3<f32>) -> f32 {
+    move |p| {
+        let q = (p - center).abs() - half_size;
+        q.sup(&Vector3::zeros()).norm() + q.max().min(0.0)
+    }
+}
+
+// union blending the two surfaces where they are closer than k
+pub fn smooth_union(a: impl Fn(&Vector3<f32>) -> f32, b: impl Fn(&Vector3<f32>) -> f32, k: f32) -> impl Fn(&Vector3<f32>) -> f32 {
+    move |p| {
+        let (da, db) = (a(p), b(p));
+        let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+        db + (da - db) * h - k * h * (1.0 - h)
+    }
+}
+
+// twists space around the vertical axis through center by rate radians per unit of height.
+// Twisting stretches distances, so they are halved to keep the marching from overshooting.
+pub fn twist(f: impl Fn(&Vector3<f32>) -> f32, center: Vector3<f32>, rate: f32) -> impl Fn(&Vector3<f32>) -> f32 {
+    move |p| {
+        let q = p - center;
+        let (sin, cos) = (rate * q.y).sin_cos();
+        let twisted = Vector3::new(cos * q.x - sin * q.z, q.y, sin * q.x + cos * q.z);
+        0.5 * f(&(center + twisted))
+    }
+}
+
+// repeats the cell of f around the origin infinitely, with the given period along each axis
+pub fn repeat(f: impl Fn(&Vector3<f32>) -> f32, period: Vector3<f32>) -> impl Fn(&Vector3<f32>) -> f32 {
+    move |p| {
+        let q = p.zip_map(&period, |x, c| x - c * (x / c).round());
+        f(&q)
+    }
+}
@@ -0,0 +1,87 @@
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::texture::Texture;
+use crate::aabb::AABB;
+
+// finite difference steps for bump mapping, in uv and in world units
+const UV_DELTA: f32 = 0.0005;
+const WORLD_DELTA: f32 = 0.0005;
+
+// orthonormal frame around the shading normal, with the tangent following increasing u
+fn tangent_frame(hit: &HitRecord) -> (Vector3<f32>, Vector3<f32>) {
+    let tangent = hit.tangent - hit.tangent.dot(&hit.normal) * hit.normal;
+    let tangent = if tangent.norm_squared() > 0.0 {
+        tangent.normalize()
+    } else {
+        let axis = if hit.normal.x.abs() > 0.9 { Vector3::y() } else { Vector3::x() };
+        hit.normal.cross(&axis).normalize()
+    };
+    // towards increasing v, also on surfaces whose uv are mirrored
+    let bitangent = hit.bitangent_sign * hit.normal.cross(&tangent);
+    (tangent, bitangent)
+}
+
+pub struct NormalMap<H: Hitable, T: Texture> {
+    hitable: H,
+    texture: T
+}
+
+impl<H: Hitable, T: Texture> NormalMap<H, T> {
+    pub fn new(hitable: H, texture: T) -> Self { NormalMap { hitable, texture } }
+}
+
+impl<H: Hitable, T: Texture> Hitable for NormalMap<H, T> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.hitable.hit(ray, t_min, t_max).map(|mut hit| {
+            let (tangent, bitangent) = tangent_frame(&hit);
+            // texel colors in [0, 1] encode tangent space components in [-1, 1]
+            let n = 2.0 * self.texture.sample(&hit) - Vector3::new(1.0, 1.0, 1.0);
+            let normal = n.x * tangent + n.y * bitangent + n.z * hit.normal;
+            if normal.norm_squared() > 0.0 {
+                hit.normal = normal.normalize();
+                hit.tangent = tangent;
+            }
+            hit
+        })
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> { self.hitable.bounding_box(t0, t1) }
+}
+
+pub struct BumpMap<H: Hitable, T: Texture> {
+    hitable: H,
+    texture: T,
+    scale: f32
+}
+
+impl<H: Hitable, T: Texture> BumpMap<H, T> {
+    // scale converts the slope of the texture values into a slope of the surface. the slope of a
+    // texture looked up by uv is taken per unit of uv, and the slope of a solid texture per
+    // world unit, so a scale of 1 gives surface heights of the texture value per world unit
+    // for solid textures and per unit of uv for uv mapped ones
+    pub fn new(hitable: H, texture: T, scale: f32) -> Self { BumpMap { hitable, texture, scale } }
+}
+
+impl<H: Hitable, T: Texture> Hitable for BumpMap<H, T> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.hitable.hit(ray, t_min, t_max).map(|mut hit| {
+            let (tangent, bitangent) = tangent_frame(&hit);
+            // forward differences in uv and along the surface taken apart, each over its own
+            // step, so that the slope does not depend on how the steps compare
+            let height = self.texture.sample_scalar(&hit);
+            let slope = |uv_step: (f32, f32), world_step: Vector3<f32>| {
+                let along_uv = self.texture.sample_scalar(&HitRecord { u: hit.u + uv_step.0, v: hit.v + uv_step.1, ..hit });
+                let along_surface = self.texture.sample_scalar(&HitRecord { p: hit.p + world_step, ..hit });
+                (along_uv - height) / UV_DELTA + (along_surface - height) / WORLD_DELTA
+            };
+            let du = self.scale * slope((UV_DELTA, 0.0), WORLD_DELTA * tangent);
+            let dv = self.scale * slope((0.0, UV_DELTA), WORLD_DELTA * bitangent);
+            hit.normal = (hit.normal - du * tangent - dv * bitangent).normalize();
+            hit.tangent = tangent;
+            hit
+        })
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> { self.hitable.bounding_box(t0, t1) }
+}
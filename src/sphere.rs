@@ -14,6 +14,11 @@ fn get_sphere_uv(p: &Vector3<f32>) -> (f32, f32) {
     (u, v)
 }
 
+fn get_sphere_tangent(p: &Vector3<f32>) -> Vector3<f32> {
+    let tangent = Vector3::new(p.z, 0.0, -p.x);
+    if tangent.norm_squared() > 0.0 { tangent.normalize() } else { Vector3::x() }
+}
+
 #[derive(Clone)]
 pub struct Sphere<M: Material> {
     center: Vector3<f32>,
@@ -39,19 +44,42 @@ impl<M: Material> Hitable for Sphere<M> {
                 let p = ray.point_at_parameter(t);
                 let normal = (p - self.center) / self.radius;
                 let (u, v) = get_sphere_uv(&normal);
-                return Some(HitRecord { t, u, v, p, normal, material: &self.material })
+                let tangent = get_sphere_tangent(&normal);
+                return Some(HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: 1.0, material: &self.material })
             }
             let t = (-b + sqrt_discriminant) / a;
             if t < t_max && t > t_min {
                 let p = ray.point_at_parameter(t);
                 let normal = (p - self.center) / self.radius;
                 let (u, v) = get_sphere_uv(&normal);
-                return Some(HitRecord { t, u, v, p, normal, material: &self.material })
+                let tangent = get_sphere_tangent(&normal);
+                return Some(HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: 1.0, material: &self.material })
             }
         }
         None
     }
 
+    fn intervals(&self, ray: &Ray) -> Vec<(HitRecord, HitRecord)> {
+        let oc = ray.origin() - self.center;
+        let a = ray.direction().dot(&ray.direction());
+        let b = oc.dot(&ray.direction());
+        let c = oc.dot(&oc) - self.radius.powi(2);
+        let discriminant = b.powi(2) - a * c;
+        if discriminant > 0.0 {
+            let sqrt_discriminant = discriminant.sqrt();
+            let hit = |t: f32| {
+                let p = ray.point_at_parameter(t);
+                let normal = (p - self.center) / self.radius;
+                let (u, v) = get_sphere_uv(&normal);
+                let tangent = get_sphere_tangent(&normal);
+                HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: 1.0, material: &self.material }
+            };
+            vec![(hit((-b - sqrt_discriminant) / a), hit((-b + sqrt_discriminant) / a))]
+        } else {
+            Vec::new()
+        }
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         let radius = Vector3::new(self.radius, self.radius, self. radius);
         let min = self.center - radius;
@@ -94,14 +122,16 @@ impl<M: Material> Hitable for MovingSphere<M> {
                 let p = ray.point_at_parameter(t);
                 let normal = (p - center) / self.radius;
                 let (u, v) = get_sphere_uv(&normal);
-                return Some(HitRecord { t, u, v, p, normal, material: &self.material })
+                let tangent = get_sphere_tangent(&normal);
+                return Some(HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: 1.0, material: &self.material })
             }
             let t = (-b + sqrt_discriminant) / a;
             if t < t_max && t > t_min {
                 let p = ray.point_at_parameter(t);
                 let normal = (p - center) / self.radius;
                 let (u, v) = get_sphere_uv(&normal);
-                return Some(HitRecord { t, u, v, p, normal, material: &self.material })
+                let tangent = get_sphere_tangent(&normal);
+                return Some(HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: 1.0, material: &self.material })
             }
         }
         None
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use nalgebra::Vector3;
+use crate::material::Material;
+use crate::mesh::{self, Mesh};
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) { if a < b { (a, b) } else { (b, a) } }
+
+// a polygon mesh used as the control cage of a subdivision surface, faces list their
+// vertices counter clockwise seen from outside
+pub struct ControlCage {
+    positions: Vec<Vector3<f32>>,
+    faces: Vec<Vec<usize>>
+}
+
+impl ControlCage {
+    pub fn new(positions: Vec<Vector3<f32>>, faces: Vec<Vec<usize>>) -> Self {
+        assert!(faces.iter().all(|f| f.len() >= 3), "control cage faces need at least 3 vertices");
+        ControlCage { positions, faces }
+    }
+
+    // the faces around every edge
+    fn edges(&self) -> HashMap<(usize, usize), Vec<usize>> {
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                edges.entry(edge_key(face[i], face[(i + 1) % face.len()])).or_default().push(f);
+            }
+        }
+        edges
+    }
+
+    // neighbours of every vertex along any edge, and along boundary edges only
+    fn neighbours(&self, edges: &HashMap<(usize, usize), Vec<usize>>) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut all = vec![Vec::new(); self.positions.len()];
+        let mut boundary = vec![Vec::new(); self.positions.len()];
+        for (&(a, b), faces) in edges {
+            all[a].push(b);
+            all[b].push(a);
+            if faces.len() == 1 {
+                boundary[a].push(b);
+                boundary[b].push(a);
+            }
+        }
+        (all, boundary)
+    }
+
+    // on a boundary the surface follows the cubic B-spline of the boundary edges
+    fn boundary_vertex(&self, v: usize, boundary: &[usize]) -> Vector3<f32> {
+        let p = self.positions[v];
+        if boundary.len() == 2 {
+            0.75 * p + 0.125 * (self.positions[boundary[0]] + self.positions[boundary[1]])
+        } else {
+            // a corner, or a non manifold vertex
+            p
+        }
+    }
+
+    // fan triangulation of every face
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        self.faces.iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |i| [face[0], face[i], face[i + 1]]))
+            .collect()
+    }
+
+    // applies level steps of Catmull-Clark subdivision, every face becomes quads
+    pub fn catmull_clark(&self, level: usize) -> ControlCage {
+        (0..level).fold(ControlCage::new(self.positions.clone(), self.faces.clone()), |cage, _| cage.catmull_clark_step())
+    }
+
+    fn catmull_clark_step(&self) -> ControlCage {
+        let edges = self.edges();
+        let (neighbours, boundary) = self.neighbours(&edges);
+        let face_points: Vec<Vector3<f32>> = self.faces.iter()
+            .map(|face| face.iter().map(|&v| self.positions[v]).sum::<Vector3<f32>>() / face.len() as f32)
+            .collect();
+        // new vertices are the moved old vertices, then the edge points, then the face points
+        let mut positions: Vec<Vector3<f32>> = Vec::with_capacity(self.positions.len() + edges.len() + self.faces.len());
+        let mut vertex_faces = vec![Vec::new(); self.positions.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in face {
+                vertex_faces[v].push(f);
+            }
+        }
+        for (v, p) in self.positions.iter().enumerate() {
+            if !boundary[v].is_empty() {
+                positions.push(self.boundary_vertex(v, &boundary[v]));
+            } else if vertex_faces[v].is_empty() {
+                positions.push(*p);
+            } else {
+                let n = vertex_faces[v].len() as f32;
+                let f = vertex_faces[v].iter().map(|&f| face_points[f]).sum::<Vector3<f32>>() / n;
+                let r = neighbours[v].iter().map(|&w| 0.5 * (p + self.positions[w])).sum::<Vector3<f32>>() / neighbours[v].len() as f32;
+                positions.push((f + 2.0 * r + (n - 3.0) * p) / n);
+            }
+        }
+        let mut edge_points = HashMap::new();
+        for (&(a, b), faces) in &edges {
+            let midpoint = 0.5 * (self.positions[a] + self.positions[b]);
+            let point = if faces.len() == 2 {
+                0.5 * midpoint + 0.25 * (face_points[faces[0]] + face_points[faces[1]])
+            } else {
+                midpoint
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(point);
+        }
+        let face_start = positions.len();
+        positions.extend(face_points);
+        let faces = self.faces.iter().enumerate()
+            .flat_map(|(f, face)| {
+                let n = face.len();
+                let edge_points = &edge_points;
+                (0..n).map(move |i| vec![
+                    face[i],
+                    edge_points[&edge_key(face[i], face[(i + 1) % n])],
+                    face_start + f,
+                    edge_points[&edge_key(face[(i + n - 1) % n], face[i])]
+                ])
+            })
+            .collect();
+        ControlCage { positions, faces }
+    }
+
+    // applies level steps of Loop subdivision, faces that are not triangles are triangulated first
+    pub fn loop_subdivision(&self, level: usize) -> ControlCage {
+        let faces = self.triangles().into_iter().map(|t| t.to_vec()).collect();
+        (0..level).fold(ControlCage::new(self.positions.clone(), faces), |cage, _| cage.loop_step())
+    }
+
+    fn loop_step(&self) -> ControlCage {
+        let edges = self.edges();
+        let (neighbours, boundary) = self.neighbours(&edges);
+        let mut positions: Vec<Vector3<f32>> = Vec::with_capacity(self.positions.len() + edges.len());
+        for (v, p) in self.positions.iter().enumerate() {
+            if !boundary[v].is_empty() {
+                positions.push(self.boundary_vertex(v, &boundary[v]));
+            } else if neighbours[v].is_empty() {
+                positions.push(*p);
+            } else {
+                let n = neighbours[v].len();
+                let beta = if n == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n as f32) };
+                let sum = neighbours[v].iter().map(|&w| self.positions[w]).sum::<Vector3<f32>>();
+                positions.push((1.0 - n as f32 * beta) * p + beta * sum);
+            }
+        }
+        let mut edge_points = HashMap::new();
+        for (&(a, b), faces) in &edges {
+            let point = if faces.len() == 2 {
+                // the vertices facing the edge in its two triangles
+                let opposite = |f: usize| *self.faces[f].iter().find(|&&v| v != a && v != b).unwrap();
+                0.375 * (self.positions[a] + self.positions[b]) +
+                    0.125 * (self.positions[opposite(faces[0])] + self.positions[opposite(faces[1])])
+            } else {
+                0.5 * (self.positions[a] + self.positions[b])
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(point);
+        }
+        let faces = self.faces.iter()
+            .flat_map(|face| {
+                let [a, b, c] = [face[0], face[1], face[2]];
+                let (ab, bc, ca) = (edge_points[&edge_key(a, b)], edge_points[&edge_key(b, c)], edge_points[&edge_key(c, a)]);
+                vec![vec![a, ab, ca], vec![b, bc, ab], vec![c, ca, bc], vec![ab, bc, ca]]
+            })
+            .collect();
+        ControlCage { positions, faces }
+    }
+
+    // a triangle mesh of the faces with smooth vertex normals, ready to be put in a BVH
+    pub fn to_mesh<M: Material + 'static>(&self, material: M) -> Mesh {
+        let triangles = self.triangles();
+        let normals = mesh::vertex_normals(&self.positions, &triangles);
+        Mesh::new(self.positions.clone(), normals, triangles, material)
+    }
+}
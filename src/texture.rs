@@ -1,8 +1,26 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 use nalgebra::Vector3;
+use image::{DynamicImage, ImageResult, RgbImage, Rgb32FImage};
 use crate::perlin::Perlin;
+use crate::hitable::HitRecord;
+use crate::color;
 
-pub trait Texture: Sync {
+pub trait Texture: Send + Sync {
     fn value(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32>;
+
+    fn scalar(&self, u: f32, v: f32, p: &Vector3<f32>) -> f32 { self.value(u, v, p).mean() }
+
+    // the texture at a surface hit, for textures that need more of it than uv and position
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> { self.value(hit.u, hit.v, &hit.p) }
+
+    fn sample_scalar(&self, hit: &HitRecord) -> f32 { self.sample(hit).mean() }
+}
+
+// a scalar at every point in space, like a density, a temperature or a noise
+pub trait ScalarField: Send + Sync {
+    fn at(&self, p: &Vector3<f32>) -> f32;
 }
 
 #[derive(Clone)]
@@ -38,6 +56,168 @@ impl<T: Texture, U: Texture> Texture for CheckerTexture<T, U> {
         let sines = f32::sin(10.0 * p.x) * f32::sin(10.0 * p.y) * f32::sin(10.0 * p.z);
         if sines < 0.0 { self.odd.value(u, v, p) } else { self.even.value(u, v, p) }
     }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> {
+        let p = hit.p;
+        let sines = f32::sin(10.0 * p.x) * f32::sin(10.0 * p.y) * f32::sin(10.0 * p.z);
+        if sines < 0.0 { self.odd.sample(hit) } else { self.even.sample(hit) }
+    }
+}
+
+// the combinators below pass whole hits on to the textures they combine, so that they work on
+// textures that need them
+
+// a blended towards b by the factor texture, clamped to [0, 1]
+#[derive(Clone)]
+pub struct MixTexture<A: Texture, B: Texture, F: Texture> {
+    a: A,
+    b: B,
+    factor: F
+}
+
+impl<A: Texture, B: Texture, F: Texture> MixTexture<A, B, F> {
+    pub fn new(a: A, b: B, factor: F) -> Self { MixTexture { a, b, factor } }
+}
+
+impl<A: Texture, B: Texture, F: Texture> Texture for MixTexture<A, B, F> {
+    fn value(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        self.a.value(u, v, p).lerp(&self.b.value(u, v, p), self.factor.scalar(u, v, p).clamp(0.0, 1.0))
+    }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> {
+        self.a.sample(hit).lerp(&self.b.sample(hit), self.factor.sample_scalar(hit).clamp(0.0, 1.0))
+    }
+}
+
+// the product of two textures per channel, with a constant texture it scales the other
+#[derive(Clone)]
+pub struct MultiplyTexture<A: Texture, B: Texture> {
+    a: A,
+    b: B
+}
+
+impl<A: Texture, B: Texture> MultiplyTexture<A, B> {
+    pub fn new(a: A, b: B) -> Self { MultiplyTexture { a, b } }
+}
+
+impl<A: Texture, B: Texture> Texture for MultiplyTexture<A, B> {
+    fn value(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        self.a.value(u, v, p).component_mul(&self.b.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> { self.a.sample(hit).component_mul(&self.b.sample(hit)) }
+}
+
+#[derive(Clone)]
+pub struct AddTexture<A: Texture, B: Texture> {
+    a: A,
+    b: B
+}
+
+impl<A: Texture, B: Texture> AddTexture<A, B> {
+    pub fn new(a: A, b: B) -> Self { AddTexture { a, b } }
+}
+
+impl<A: Texture, B: Texture> Texture for AddTexture<A, B> {
+    fn value(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32> { self.a.value(u, v, p) + self.b.value(u, v, p) }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> { self.a.sample(hit) + self.b.sample(hit) }
+}
+
+// every channel mapped linearly from the range from onto the range to, and clamped to it
+#[derive(Clone)]
+pub struct RemapTexture<T: Texture> {
+    texture: T,
+    from: (f32, f32),
+    to: (f32, f32)
+}
+
+impl<T: Texture> RemapTexture<T> {
+    pub fn new(texture: T, from: (f32, f32), to: (f32, f32)) -> Self { RemapTexture { texture, from, to } }
+
+    fn remap(&self, color: Vector3<f32>) -> Vector3<f32> {
+        color.map(|c| {
+            let t = ((c - self.from.0) / (self.from.1 - self.from.0)).clamp(0.0, 1.0);
+            self.to.0 + t * (self.to.1 - self.to.0)
+        })
+    }
+}
+
+impl<T: Texture> Texture for RemapTexture<T> {
+    fn value(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32> { self.remap(self.texture.value(u, v, p)) }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> { self.remap(self.texture.sample(hit)) }
+}
+
+// uv scaled, then rotated by an angle in degrees around the origin, then offset, before the lookup
+#[derive(Clone)]
+pub struct UvTransform<T: Texture> {
+    texture: T,
+    scale: (f32, f32),
+    sin_theta: f32,
+    cos_theta: f32,
+    offset: (f32, f32)
+}
+
+impl<T: Texture> UvTransform<T> {
+    pub fn new(texture: T, scale: (f32, f32), angle: f32, offset: (f32, f32)) -> Self {
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        UvTransform { texture, scale, sin_theta, cos_theta, offset }
+    }
+
+    fn transform(&self, u: f32, v: f32) -> (f32, f32) {
+        let (u, v) = (u * self.scale.0, v * self.scale.1);
+        (self.cos_theta * u - self.sin_theta * v + self.offset.0, self.sin_theta * u + self.cos_theta * v + self.offset.1)
+    }
+}
+
+impl<T: Texture> Texture for UvTransform<T> {
+    fn value(&self, u: f32, v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        let (u, v) = self.transform(u, v);
+        self.texture.value(u, v, p)
+    }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> {
+        let (u, v) = self.transform(hit.u, hit.v);
+        self.texture.sample(&HitRecord { u, v, ..*hit })
+    }
+}
+
+// the texture projected along the three axes, with uv from the position times scale, and blended
+// by the normal raised to sharpness. for objects without good uv. lookups without a normal blend
+// the projections equally
+#[derive(Clone)]
+pub struct TriplanarTexture<T: Texture> {
+    texture: T,
+    scale: f32,
+    sharpness: f32
+}
+
+impl<T: Texture> TriplanarTexture<T> {
+    pub fn new(texture: T, scale: f32, sharpness: f32) -> Self { TriplanarTexture { texture, scale, sharpness } }
+
+    // lookup lets the inner texture be read at the projected uv either by value or by sample
+    fn project(&self, p: &Vector3<f32>, normal: &Vector3<f32>, lookup: impl Fn(f32, f32) -> Vector3<f32>) -> Vector3<f32> {
+        // relative to the largest component, so that high sharpness does not underflow them all
+        let n = normal.abs();
+        let weights = (n / n.max().max(f32::MIN_POSITIVE)).map(|w| w.powf(self.sharpness));
+        let q = self.scale * p;
+        // u and v run along the other two axes, y is kept upright on the sides
+        let along = [(q.z, q.y), (q.x, q.z), (q.x, q.y)];
+        along.iter().zip(weights.iter())
+            .map(|(&(u, v), &w)| w * lookup(u, v))
+            .sum::<Vector3<f32>>() / weights.sum().max(f32::MIN_POSITIVE)
+    }
+}
+
+impl<T: Texture> Texture for TriplanarTexture<T> {
+    fn value(&self, _u: f32, _v: f32, p: &Vector3<f32>) -> Vector3<f32> {
+        self.project(p, &Vector3::new(1.0, 1.0, 1.0), |u, v| self.texture.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &HitRecord) -> Vector3<f32> {
+        self.project(&hit.p, &hit.normal, |u, v| self.texture.sample(&HitRecord { u, v, ..*hit }))
+    }
 }
 
 #[derive(Clone)]
@@ -47,7 +227,10 @@ pub struct NoiseTexture {
 }
 
 impl NoiseTexture {
+    #[allow(dead_code)]
     pub fn new(scale: f32) -> Self { NoiseTexture { noise: Perlin::new(), scale }}
+
+    pub fn with_seed(scale: f32, seed: u64) -> Self { NoiseTexture { noise: Perlin::with_seed(seed), scale }}
 }
 
 impl Texture for NoiseTexture {
@@ -56,29 +239,302 @@ impl Texture for NoiseTexture {
     }
 }
 
+// how the values of an image are encoded. colour images are usually sRGB, while data like
+// roughness, heights or normals is stored linearly
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear
+}
+
+// how an image texture reconstructs colours between its texels
+#[derive(Clone, Copy)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    // a fixed blur, bilinear lookups in the two mip levels around the given level blended
+    // linearly, where a level of n averages about 2^n texels across. this is not trilinear
+    // filtering: rays carry no differentials, so no filter here picks the level from the
+    // footprint of a pixel, and textures seen far away or at grazing angles still alias
+    Prefiltered(f32)
+}
+
+// how an image texture continues outside [0, 1]
+#[derive(Clone, Copy)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror
+}
+
+impl Wrap {
+    fn texel(&self, i: isize, n: usize) -> usize {
+        let n = n as isize;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n { i } else { 2 * n - 1 - i }
+            }
+        };
+        i as usize
+    }
+}
+
+// the full resolution level is converted from the decoded image in square tiles of this size
+const TILE_SIZE: usize = 64;
+
+// a decoded image, kept at its own bit depth until its texels are needed
+enum Source {
+    Rgb8(RgbImage),
+    Rgb32F(Rgb32FImage)
+}
+
+impl Source {
+    fn open(path: &Path) -> ImageResult<Self> { Ok(Source::from_image(&image::open(path)?)) }
+
+    // images of up to 8 bits per channel stay 8 bit, deeper and float images are widened to float
+    fn from_image(image: &DynamicImage) -> Self {
+        match image {
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_) |
+            DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => Source::Rgb8(image.to_rgb8()),
+            _ => Source::Rgb32F(image.to_rgb32f())
+        }
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let (width, height) = match self {
+            Source::Rgb8(image) => image.dimensions(),
+            Source::Rgb32F(image) => image.dimensions()
+        };
+        (width as usize, height as usize)
+    }
+
+    fn texel(&self, i: usize, j: usize) -> Vector3<f32> {
+        match self {
+            Source::Rgb8(image) => {
+                let p = image.get_pixel(i as u32, j as u32);
+                Vector3::new(p[0] as f32, p[1] as f32, p[2] as f32) / 255.0
+            }
+            Source::Rgb32F(image) => {
+                let p = image.get_pixel(i as u32, j as u32);
+                // float images may hold infinities and NaNs, which are kept out of the filtering
+                Vector3::new(p[0], p[1], p[2]).map(|c| if c.is_finite() { c.max(0.0) } else { 0.0 })
+            }
+        }
+    }
+}
+
+// the texels of a level of size above that texel i of the level below covers, with their weights.
+// an even size halves into pairs, while an odd size 2n + 1 spreads over n texels that each cover
+// parts of three, so that no row or column is left out
+fn reduction_taps(i: usize, above: usize) -> [(usize, f32); 3] {
+    if above == 1 {
+        [(0, 1.0), (0, 0.0), (0, 0.0)]
+    } else if above % 2 == 0 {
+        [(2 * i, 0.5), (2 * i + 1, 0.5), (0, 0.0)]
+    } else {
+        let n = (above / 2) as f32;
+        let i_f = i as f32;
+        let span = above as f32;
+        [(2 * i, (n - i_f) / span), (2 * i + 1, n / span), (2 * i + 2, (i_f + 1.0) / span)]
+    }
+}
+
+// the texels of an image in one colour space, shared by every texture made from it. the decoded
+// image stays in memory, its full resolution level is converted to linear floats a tile at a time
+// and each smaller mip level is built, the first time a lookup needs them
+pub struct ImageData {
+    source: Arc<Source>,
+    width: usize,
+    height: usize,
+    color_space: ColorSpace,
+    tiles: Vec<OnceLock<Vec<Vector3<f32>>>>,
+    // mip levels from the second on, each half the size of the one before
+    levels: Vec<OnceLock<Vec<Vector3<f32>>>>
+}
+
+impl ImageData {
+    fn new(source: Arc<Source>, color_space: ColorSpace) -> Self {
+        let (width, height) = source.dimensions();
+        assert!(width > 0 && height > 0, "image has no texels");
+        let tiles = (0..width.div_ceil(TILE_SIZE) * height.div_ceil(TILE_SIZE)).map(|_| OnceLock::new()).collect();
+        // halving down to a single texel along the longer side
+        let level_count = (usize::BITS - width.max(height).leading_zeros()) as usize;
+        let levels = (1..level_count).map(|_| OnceLock::new()).collect();
+        ImageData { source, width, height, color_space, tiles, levels }
+    }
+
+    fn level_count(&self) -> usize { self.levels.len() + 1 }
+
+    fn level_size(&self, level: usize) -> (usize, usize) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    fn tile(&self, ti: usize, tj: usize) -> &[Vector3<f32>] {
+        self.tiles[ti + tj * self.width.div_ceil(TILE_SIZE)].get_or_init(|| {
+            let (i0, j0) = (ti * TILE_SIZE, tj * TILE_SIZE);
+            let (i1, j1) = ((i0 + TILE_SIZE).min(self.width), (j0 + TILE_SIZE).min(self.height));
+            (j0..j1)
+                .flat_map(|j| (i0..i1).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let t = self.source.texel(i, j);
+                    // decoded before filtering, which only makes sense on linear values
+                    match self.color_space {
+                        ColorSpace::Srgb => t.map(color::srgb_to_linear),
+                        ColorSpace::Linear => t
+                    }
+                })
+                .collect()
+        })
+    }
+
+    fn level(&self, level: usize) -> &[Vector3<f32>] {
+        self.levels[level - 1].get_or_init(|| {
+            let (width, height) = self.level_size(level);
+            let (above_width, above_height) = self.level_size(level - 1);
+            // weighted averages of the texels of the level above each texel covers
+            (0..height)
+                .flat_map(|j| (0..width).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let (along_x, along_y) = (reduction_taps(i, above_width), reduction_taps(j, above_height));
+                    along_y.iter()
+                        .flat_map(|&(y, wy)| along_x.iter().map(move |&(x, wx)| (x, y, wx * wy)))
+                        .filter(|&(_, _, w)| w > 0.0)
+                        .map(|(x, y, w)| w * self.texel(level - 1, x, y))
+                        .sum()
+                })
+                .collect()
+        })
+    }
+
+    fn texel(&self, level: usize, i: usize, j: usize) -> Vector3<f32> {
+        if level == 0 {
+            let (ti, tj) = (i / TILE_SIZE, j / TILE_SIZE);
+            let tile_width = (self.width - ti * TILE_SIZE).min(TILE_SIZE);
+            self.tile(ti, tj)[i % TILE_SIZE + (j % TILE_SIZE) * tile_width]
+        } else {
+            self.level(level)[i + j * self.level_size(level).0]
+        }
+    }
+}
+
+// an image with the way it is looked up. the texels are shared, so clones are cheap
 #[derive(Clone)]
 pub struct ImageTexture {
-    data: Vec<u8>,
-    nx: u32,
-    ny: u32
+    data: Arc<ImageData>,
+    filter: Filter,
+    wrap: Wrap,
+    scale: (f32, f32),
+    offset: (f32, f32)
 }
 
 impl ImageTexture {
-    pub fn new(data: Vec<u8>, nx: u32, ny: u32) -> Self { ImageTexture { data, nx, ny } }
+    // 8 bit sRGB colour data, row major from the top row. data like heights or normals is not
+    // sRGB encoded and goes through from_image with ColorSpace::Linear
+    #[allow(dead_code)]
+    pub fn new(data: Vec<u8>, nx: u32, ny: u32) -> Self {
+        let image = RgbImage::from_raw(nx, ny, data).expect("image data does not match its size");
+        ImageTexture::from_data(Arc::new(ImageData::new(Arc::new(Source::Rgb8(image)), ColorSpace::Srgb)))
+    }
+
+    // any image, grayscale is spread over the channels and alpha is dropped
+    pub fn from_image(image: &DynamicImage, color_space: ColorSpace) -> Self {
+        ImageTexture::from_data(Arc::new(ImageData::new(Arc::new(Source::from_image(image)), color_space)))
+    }
+
+    // a Radiance .hdr or OpenEXR image, whose linear values may go beyond 1
+    #[allow(dead_code)]
+    pub fn open_hdr<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        Ok(ImageTexture::from_image(&image::open(path)?, ColorSpace::Linear))
+    }
+
+    fn from_data(data: Arc<ImageData>) -> Self {
+        ImageTexture { data, filter: Filter::Nearest, wrap: Wrap::Clamp, scale: (1.0, 1.0), offset: (0.0, 0.0) }
+    }
+
+    pub fn with_filter(self, filter: Filter) -> Self { ImageTexture { filter, ..self } }
+
+    pub fn with_wrap(self, wrap: Wrap) -> Self { ImageTexture { wrap, ..self } }
+
+    // uv are scaled, then offset, before the lookup
+    pub fn with_uv_transform(self, scale: (f32, f32), offset: (f32, f32)) -> Self { ImageTexture { scale, offset, ..self } }
+
+    fn texel(&self, level: usize, i: isize, j: isize) -> Vector3<f32> {
+        let (width, height) = self.data.level_size(level);
+        self.data.texel(level, self.wrap.texel(i, width), self.wrap.texel(j, height))
+    }
+
+    fn nearest(&self, level: usize, u: f32, v: f32) -> Vector3<f32> {
+        let (width, height) = self.data.level_size(level);
+        let i = (u * width as f32).floor() as isize;
+        let j = ((1.0 - v) * height as f32).floor() as isize;
+        self.texel(level, i, j)
+    }
+
+    fn bilinear(&self, level: usize, u: f32, v: f32) -> Vector3<f32> {
+        let (width, height) = self.data.level_size(level);
+        // texel centers are at half integer coordinates
+        let x = u * width as f32 - 0.5;
+        let y = (1.0 - v) * height as f32 - 0.5;
+        let (i, j) = (x.floor(), y.floor());
+        let (fx, fy) = (x - i, y - j);
+        let (i, j) = (i as isize, j as isize);
+        let top = self.texel(level, i, j).lerp(&self.texel(level, i + 1, j), fx);
+        let bottom = self.texel(level, i, j + 1).lerp(&self.texel(level, i + 1, j + 1), fx);
+        top.lerp(&bottom, fy)
+    }
 }
 
 impl Texture for ImageTexture {
     fn value(&self, u: f32, v: f32, _p: &Vector3<f32>) -> Vector3<f32> {
-        let nx = self.nx as usize;
-        let ny = self.ny as usize;
-        let mut i = (u * nx as f32) as usize;
-        let mut j = ((1.0 - v) * ny as f32) as usize;
-        if i > nx - 1 { i = nx - 1 }
-        if j > ny - 1 { j = ny - 1 }
-        let idx = 3 * i + 3 * nx * j;
-        let r = self.data[idx] as f32 / 255.0;
-        let g = self.data[idx + 1] as f32 / 255.0;
-        let b = self.data[idx + 2] as f32 / 255.0;
-        Vector3::new(r, g, b)
+        let u = u * self.scale.0 + self.offset.0;
+        let v = v * self.scale.1 + self.offset.1;
+        match self.filter {
+            Filter::Nearest => self.nearest(0, u, v),
+            Filter::Bilinear => self.bilinear(0, u, v),
+            Filter::Prefiltered(lod) => {
+                let level_count = self.data.level_count();
+                let lod = lod.clamp(0.0, (level_count - 1) as f32);
+                let level = lod.floor() as usize;
+                let fine = self.bilinear(level, u, v);
+                if level + 1 < level_count {
+                    fine.lerp(&self.bilinear(level + 1, u, v), lod - level as f32)
+                } else {
+                    fine
+                }
+            }
+        }
+    }
+}
+
+// loads every image file once and hands out textures sharing its texels. a file loaded in both
+// colour spaces shares its decoded image, with texels converted for each
+#[derive(Default)]
+pub struct TextureCache {
+    sources: HashMap<PathBuf, Arc<Source>>,
+    images: HashMap<(PathBuf, ColorSpace), Arc<ImageData>>
+}
+
+impl TextureCache {
+    // files are decoded on their first load, so that missing or broken ones are reported here.
+    // the texels are converted from the decoded image later, as lookups reach them
+    pub fn load<P: AsRef<Path>>(&mut self, path: P, color_space: ColorSpace) -> ImageResult<ImageTexture> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(data) = self.images.get(&(path.clone(), color_space)) {
+            return Ok(ImageTexture::from_data(data.clone()))
+        }
+        let source = match self.sources.get(&path) {
+            Some(source) => source.clone(),
+            None => {
+                let source = Arc::new(Source::open(&path)?);
+                self.sources.insert(path.clone(), source.clone());
+                source
+            }
+        };
+        let data = Arc::new(ImageData::new(source, color_space));
+        self.images.insert((path, color_space), data.clone());
+        Ok(ImageTexture::from_data(data))
     }
 }
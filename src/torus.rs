@@ -0,0 +1,132 @@
+use std::f32;
+use std::f64;
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::material::Material;
+use crate::aabb::AABB;
+
+// real roots of x^3 + a * x^2 + b * x + c
+fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // depressed to y^3 + p * y + q with x = y - a / 3
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a.powi(3) / 27.0 - a * b / 3.0 + c;
+    let shift = -a / 3.0;
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+    if discriminant > 0.0 {
+        let sqrt_discriminant = discriminant.sqrt();
+        vec![(-q / 2.0 + sqrt_discriminant).cbrt() + (-q / 2.0 - sqrt_discriminant).cbrt() + shift]
+    } else if p == 0.0 {
+        vec![shift]
+    } else {
+        let r = (-p / 3.0).sqrt();
+        let phi = (-q / (2.0 * r.powi(3))).clamp(-1.0, 1.0).acos();
+        (0..3).map(|k| 2.0 * r * ((phi + 2.0 * f64::consts::PI * k as f64) / 3.0).cos() + shift).collect()
+    }
+}
+
+// real roots of x^4 + a * x^3 + b * x^2 + c * x + d, by Ferrari's method
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // depressed to y^4 + p * y^2 + q * y + r with x = y - a / 4
+    let p = b - 3.0 * a * a / 8.0;
+    let q = a.powi(3) / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 * a.powi(4) / 256.0 + a * a * b / 16.0 - a * c / 4.0 + d;
+    let shift = -a / 4.0;
+    let mut roots = Vec::new();
+    let mut solve_quadratic = |b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let sqrt_discriminant = discriminant.sqrt();
+            roots.push((-b - sqrt_discriminant) / 2.0 + shift);
+            roots.push((-b + sqrt_discriminant) / 2.0 + shift);
+        }
+    };
+    if q.abs() < 1e-12 {
+        // biquadratic
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            for z in [(-p - discriminant.sqrt()) / 2.0, (-p + discriminant.sqrt()) / 2.0] {
+                if z >= 0.0 {
+                    solve_quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        // a root m > 0 of the resolvent cubic splits the quartic into two quadratics
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0).into_iter().fold(f64::MIN, f64::max);
+        if m > 0.0 {
+            let s = (2.0 * m).sqrt();
+            solve_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+            solve_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        }
+    }
+    // polish the roots, the closed form loses precision when the coefficients differ a lot
+    roots.iter().map(|&x| {
+        let mut x = x;
+        for _ in 0..2 {
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+            if df != 0.0 { x -= f / df }
+        }
+        x
+    }).collect()
+}
+
+// a torus around the vertical axis through center, with the tube of minor_radius
+// running along a circle of major_radius
+pub struct Torus<M: Material> {
+    center: Vector3<f32>,
+    major_radius: f32,
+    minor_radius: f32,
+    material: M
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(center: Vector3<f32>, major_radius: f32, minor_radius: f32, material: M) -> Self {
+        Torus { center, major_radius, minor_radius, material }
+    }
+}
+
+impl<M: Material> Hitable for Torus<M> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        // solved in double precision along the normalized direction
+        let length = ray.direction().norm() as f64;
+        let o = (ray.origin() - self.center).cast::<f64>();
+        let d = ray.direction().cast::<f64>() / length;
+        let major = (self.major_radius as f64).powi(2);
+        let minor = (self.minor_radius as f64).powi(2);
+        let e = o.dot(&o) + major - minor;
+        let f = o.dot(&d);
+        let dxz = d.x * d.x + d.z * d.z;
+        let oxz = o.x * o.x + o.z * o.z;
+        let odxz = o.x * d.x + o.z * d.z;
+        let roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - 4.0 * major * dxz,
+            4.0 * f * e - 8.0 * major * odxz,
+            e * e - 4.0 * major * oxz);
+        roots.into_iter()
+            .map(|s| (s / length) as f32)
+            .filter(|&t| t > t_min && t < t_max)
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .map(|t| {
+                let p = ray.point_at_parameter(t);
+                let local = p - self.center;
+                let radial = Vector3::new(local.x, 0.0, local.z);
+                let radial = if radial.norm_squared() > 0.0 { radial.normalize() } else { Vector3::x() };
+                let normal = (local - self.major_radius * radial).normalize();
+                let phi = local.z.atan2(local.x);
+                let theta = local.y.atan2(radial.dot(&local) - self.major_radius);
+                let u = (phi + f32::consts::PI) / (2.0 * f32::consts::PI);
+                let v = (theta + f32::consts::PI) / (2.0 * f32::consts::PI);
+                let tangent = Vector3::new(-radial.z, 0.0, radial.x);
+                // v goes up the outside of the tube, against normal x tangent
+                HitRecord { t, u, v, p, normal, geometric_normal: normal, tangent, bitangent_sign: -1.0, material: &self.material }
+            })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let extent = Vector3::new(self.major_radius + self.minor_radius, self.minor_radius, self.major_radius + self.minor_radius);
+        Some(AABB::new(self.center - extent, self.center + extent))
+    }
+}
@@ -0,0 +1,188 @@
+use std::f32;
+use std::sync::Arc;
+use nalgebra::{Matrix3, Matrix4, Point3, UnitQuaternion, Vector3};
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::aabb;
+use crate::aabb::AABB;
+
+fn transform_box(matrix: &Matrix4<f32>, bbox: &AABB) -> AABB {
+    let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vector3::new(-f32::MAX, -f32::MAX, -f32::MAX);
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let corner = Point3::new(
+                    if i == 0 { bbox.min.x } else { bbox.max.x },
+                    if j == 0 { bbox.min.y } else { bbox.max.y },
+                    if k == 0 { bbox.min.z } else { bbox.max.z });
+                let corner = matrix.transform_point(&corner).coords;
+                min = min.inf(&corner);
+                max = max.sup(&corner);
+            }
+        }
+    }
+    AABB::new(min, max)
+}
+
+fn hit_transformed<'a>(hitable: &'a dyn Hitable, ray: &Ray, t_min: f32, t_max: f32, matrix: &Matrix4<f32>, inverse: &Matrix4<f32>, normal_matrix: &Matrix3<f32>) -> Option<HitRecord<'a>> {
+    // the direction is not normalized, so t is the same in object and world space
+    let origin = inverse.transform_point(&Point3::from(ray.origin())).coords;
+    let direction = inverse.transform_vector(&ray.direction());
+    let transformed_ray = Ray::new(origin, direction, ray.time());
+    hitable.hit(&transformed_ray, t_min, t_max).map(|mut hit| {
+        hit.p = ray.point_at_parameter(hit.t);
+        hit.normal = (normal_matrix * hit.normal).normalize();
+        hit.geometric_normal = (normal_matrix * hit.geometric_normal).normalize();
+        hit.tangent = matrix.transform_vector(&hit.tangent).normalize();
+        // a mirroring transform turns the uv the other way around the normal
+        if normal_matrix.determinant() < 0.0 {
+            hit.bitangent_sign = -hit.bitangent_sign;
+        }
+        hit
+    })
+}
+
+// an instance of a shared hitable, placed in the scene by an affine transformation
+pub struct Transform {
+    matrix: Matrix4<f32>,
+    inverse: Matrix4<f32>,
+    normal_matrix: Matrix3<f32>,
+    hitable: Arc<dyn Hitable>
+}
+
+impl Transform {
+    pub fn new(hitable: Arc<dyn Hitable>, matrix: Matrix4<f32>) -> Self {
+        let inverse = matrix.try_inverse().expect("transform matrix is not invertible");
+        let normal_matrix = inverse.fixed_slice::<3, 3>(0, 0).transpose();
+        Transform { matrix, inverse, normal_matrix, hitable }
+    }
+}
+
+impl Hitable for Transform {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        hit_transformed(self.hitable.as_ref(), ray, t_min, t_max, &self.matrix, &self.inverse, &self.normal_matrix)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.hitable.bounding_box(t0, t1).map(|bbox| transform_box(&self.matrix, &bbox))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    time: f32,
+    translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+    scale: Vector3<f32>
+}
+
+impl Keyframe {
+    pub fn new(time: f32, translation: Vector3<f32>, rotation: UnitQuaternion<f32>, scale: Vector3<f32>) -> Self {
+        Keyframe { time, translation, rotation, scale }
+    }
+
+    fn interpolate(&self, other: &Keyframe, time: f32) -> Keyframe {
+        let t = (time - self.time) / (other.time - self.time);
+        let rotation = self.rotation.try_slerp(&other.rotation, t, 1.0e-6)
+            .unwrap_or_else(|| self.rotation.nlerp(&other.rotation, t));
+        Keyframe {
+            time,
+            translation: self.translation.lerp(&other.translation, t),
+            rotation,
+            scale: self.scale.lerp(&other.scale, t)
+        }
+    }
+
+    // translation * rotation * scale
+    fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation) *
+            self.rotation.to_homogeneous() *
+            Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    fn inverse(&self) -> Matrix4<f32> {
+        Matrix4::new_nonuniform_scaling(&self.scale.map(|s| 1.0 / s)) *
+            self.rotation.inverse().to_homogeneous() *
+            Matrix4::new_translation(&-self.translation)
+    }
+
+    // inverse transpose of rotation * scale
+    fn normal_matrix(&self) -> Matrix3<f32> {
+        self.rotation.to_rotation_matrix().into_inner() * Matrix3::from_diagonal(&self.scale.map(|s| 1.0 / s))
+    }
+}
+
+// an instance of a shared hitable whose transformation is interpolated between keyframes
+// at the time of each ray, giving motion blur to any hitable
+pub struct AnimatedTransform {
+    keyframes: Vec<Keyframe>,
+    hitable: Arc<dyn Hitable>
+}
+
+impl AnimatedTransform {
+    pub fn new(hitable: Arc<dyn Hitable>, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "animated transform needs at least one keyframe");
+        keyframes.sort_unstable_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        AnimatedTransform { keyframes, hitable }
+    }
+
+    fn keyframe(&self, time: f32) -> Keyframe {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            *first
+        } else if time >= last.time {
+            *last
+        } else {
+            let next = self.keyframes.iter().position(|k| k.time > time).unwrap();
+            self.keyframes[next - 1].interpolate(&self.keyframes[next], time)
+        }
+    }
+}
+
+impl Hitable for AnimatedTransform {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let keyframe = self.keyframe(ray.time());
+        hit_transformed(self.hitable.as_ref(), ray, t_min, t_max, &keyframe.matrix(), &keyframe.inverse(), &keyframe.normal_matrix())
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        const STEPS: usize = 16;
+        let bbox = self.hitable.bounding_box(t0, t1)?;
+        let corners: Vec<Vector3<f32>> = (0..8)
+            .map(|i| Vector3::new(
+                if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if i & 4 == 0 { bbox.min.z } else { bbox.max.z }))
+            .collect();
+        // steps through the shutter interval that never straddle a keyframe
+        let mut times = vec![t0, t1];
+        times.extend(self.keyframes.iter().map(|k| k.time).filter(|&t| t > t0 && t < t1));
+        times.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        let samples: Vec<Keyframe> = times.windows(2)
+            .flat_map(|w| (0..STEPS).map(move |i| w[0] + (w[1] - w[0]) * i as f32 / STEPS as f32))
+            .chain(std::iter::once(t1))
+            .map(|t| self.keyframe(t))
+            .collect();
+        let boxes: Vec<AABB> = samples.windows(2)
+            .map(|w| {
+                let (start, end) = (&w[0], &w[1]);
+                // within a step a point x moves to translation + rotation * scale * x. with the
+                // rotation held at the start that is linear in time, so it stays between the
+                // boxes at both ends of the step
+                let held = Keyframe { rotation: start.rotation, ..*end };
+                let swept = aabb::surrounding_box(&transform_box(&start.matrix(), &bbox), &transform_box(&held.matrix(), &bbox));
+                // turning by the rest of the rotation moves a point at distance r from the
+                // origin by less than r times the angle
+                let radius = corners.iter()
+                    .map(|c| f32::max(c.component_mul(&start.scale).norm(), c.component_mul(&end.scale).norm()))
+                    .fold(0.0, f32::max);
+                let padding = radius * start.rotation.angle_to(&end.rotation);
+                let padding = Vector3::new(padding, padding, padding);
+                AABB::new(swept.min - padding, swept.max + padding)
+            })
+            .collect();
+        Some(boxes.iter().skip(1).fold(boxes[0], |acc, b| aabb::surrounding_box(&acc, b)))
+    }
+}
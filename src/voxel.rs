@@ -0,0 +1,146 @@
+use std::f32;
+use nalgebra::Vector3;
+use crate::ray::Ray;
+use crate::hitable::{Hitable, HitRecord};
+use crate::material::Material;
+use crate::aabb::AABB;
+
+// a grid of cubic voxels, each either empty or holding the index of one of the materials, which
+// may be of different kinds. the grid spans dimensions voxels of voxel_size from origin, and uv
+// run across each voxel face
+pub struct VoxelGrid {
+    voxels: Vec<Option<u8>>,
+    dimensions: [usize; 3],
+    origin: Vector3<f32>,
+    voxel_size: f32,
+    bbox: AABB,
+    materials: Vec<Box<dyn Material>>
+}
+
+impl VoxelGrid {
+    pub fn new(dimensions: [usize; 3], origin: Vector3<f32>, voxel_size: f32, materials: Vec<Box<dyn Material>>) -> Self {
+        assert!(dimensions.iter().all(|&n| n > 0), "voxel grid needs at least one voxel along every axis");
+        let size = voxel_size * Vector3::new(dimensions[0] as f32, dimensions[1] as f32, dimensions[2] as f32);
+        let bbox = AABB::new(origin, origin + size);
+        let voxels = vec![None; dimensions[0] * dimensions[1] * dimensions[2]];
+        VoxelGrid { voxels, dimensions, origin, voxel_size, bbox, materials }
+    }
+
+    // a grid filled with the material index returned for every voxel
+    pub fn from_fn<F: Fn(usize, usize, usize) -> Option<u8>>(dimensions: [usize; 3], origin: Vector3<f32>, voxel_size: f32, materials: Vec<Box<dyn Material>>, f: F) -> Self {
+        let mut grid = VoxelGrid::new(dimensions, origin, voxel_size, materials);
+        for k in 0..dimensions[2] {
+            for j in 0..dimensions[1] {
+                for i in 0..dimensions[0] {
+                    grid.set(i, j, k, f(i, j, k));
+                }
+            }
+        }
+        grid
+    }
+
+    fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        i + self.dimensions[0] * (j + self.dimensions[1] * k)
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, i: usize, j: usize, k: usize) -> Option<u8> { self.voxels[self.index(i, j, k)] }
+
+    pub fn set(&mut self, i: usize, j: usize, k: usize, material: Option<u8>) {
+        if let Some(m) = material {
+            assert!((m as usize) < self.materials.len(), "voxel material index out of range");
+        }
+        let index = self.index(i, j, k);
+        self.voxels[index] = material;
+    }
+
+    // the face of a voxel the ray enters or leaves it through, with the normal facing out of it
+    fn face_hit(&self, ray: &Ray, t: f32, cell: [usize; 3], axis: usize, entering: bool, material: u8) -> HitRecord {
+        let p = ray.point_at_parameter(t);
+        let local = (p - self.origin) / self.voxel_size;
+        let fraction = |a: usize| (local[a] - cell[a] as f32).clamp(0.0, 1.0);
+        let mut normal = Vector3::zeros();
+        normal[axis] = if (ray.direction()[axis] > 0.0) == entering { -1.0 } else { 1.0 };
+        // u runs along the first and v along the second of the other axes, y is kept upright on the sides
+        let (u_axis, v_axis) = match axis {
+            0 => (2, 1),
+            1 => (0, 2),
+            _ => (0, 1)
+        };
+        let mut tangent = Vector3::zeros();
+        tangent[u_axis] = 1.0;
+        let bitangent_sign = normal.cross(&tangent)[v_axis];
+        HitRecord { t, u: fraction(u_axis), v: fraction(v_axis), p, normal, geometric_normal: normal, tangent, bitangent_sign, material: self.materials[material as usize].as_ref() }
+    }
+}
+
+impl Hitable for VoxelGrid {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t_enter, t_exit) = self.bbox.intersect(ray, t_min, t_max)?;
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let entry = ray.point_at_parameter(t_enter);
+        let mut cell = [0; 3];
+        let mut step = [0; 3];
+        let mut t_next = [f32::MAX; 3];
+        let mut t_delta = [f32::MAX; 3];
+        // the face the ray enters the grid through is on the axis whose slab it enters last
+        let mut axis = 0;
+        let mut t_slab = -f32::MAX;
+        for a in 0..3 {
+            let n = self.dimensions[a];
+            let c = (((entry[a] - self.origin[a]) / self.voxel_size).floor().max(0.0) as usize).min(n - 1);
+            cell[a] = c;
+            let d = direction[a];
+            if d > 0.0 {
+                step[a] = 1;
+                t_next[a] = (self.origin[a] + (c + 1) as f32 * self.voxel_size - origin[a]) / d;
+                t_delta[a] = self.voxel_size / d;
+            } else if d < 0.0 {
+                step[a] = -1;
+                t_next[a] = (self.origin[a] + c as f32 * self.voxel_size - origin[a]) / d;
+                t_delta[a] = -self.voxel_size / d;
+            }
+            if d != 0.0 {
+                let near = if d > 0.0 { self.bbox.min[a] } else { self.bbox.max[a] };
+                let t = (near - origin[a]) / d;
+                if t > t_slab {
+                    t_slab = t;
+                    axis = a;
+                }
+            }
+        }
+        // 3D DDA after Amanatides and Woo, visiting the voxels in the order the ray crosses them
+        let mut t = t_enter;
+        loop {
+            // the axis of the face the ray leaves the voxel through
+            let a = if t_next[0] < t_next[1] {
+                if t_next[0] < t_next[2] { 0 } else { 2 }
+            } else if t_next[1] < t_next[2] { 1 } else { 2 };
+            if let Some(material) = self.voxels[self.index(cell[0], cell[1], cell[2])] {
+                if t > t_min {
+                    return Some(self.face_hit(ray, t, cell, axis, true, material))
+                }
+                // a ray starting inside a filled voxel hits it from inside where it leaves it
+                if t_next[a] > t_min && t_next[a] < t_max {
+                    return Some(self.face_hit(ray, t_next[a], cell, a, false, material))
+                }
+            }
+            if t_next[a] > t_exit {
+                return None
+            }
+            let next = cell[a] as isize + step[a];
+            if next < 0 || next >= self.dimensions[a] as isize {
+                return None
+            }
+            cell[a] = next as usize;
+            t = t_next[a];
+            axis = a;
+            t_next[a] += t_delta[a];
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}